@@ -0,0 +1,637 @@
+//! Multipurpose clock generator and system clock dividers.
+//!
+//! A [`ClockConfig`] picks one of the MCG modes and the core clock wanted,
+//! then [`ClockConfig::freeze`] switches to it from the reset mode and
+//! returns the resulting [`Clocks`]. Drivers take the [`Clocks`] to derive
+//! their rates.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::Poll;
+
+use cortex_m_rt::interrupt;
+use embassy_sync::waitqueue::AtomicWaker;
+use pac::Interrupt as interrupt;
+
+/// Slow internal reference, trimmed at the factory. Units of Hertz.
+const SLOW_IRC_HZ: u32 = 32_768;
+/// Fast internal reference. Units of Hertz.
+const FAST_IRC_HZ: u32 = 4_000_000;
+
+/// Highest core and system clock. Units of Hertz.
+const MAX_CORE_HZ: u32 = 48_000_000;
+/// Highest bus and flash clock. Units of Hertz.
+const MAX_BUS_HZ: u32 = 24_000_000;
+
+/// FLL multiplier with the mid-low DCO range, for a 32.768 kHz reference
+/// with DMX32 set and for any other reference.
+const FLL_FACTOR_DMX32: u32 = 1464;
+const FLL_FACTOR: u32 = 1280;
+
+/// Clocks used after a failure.
+const FALLBACK: Clocks = ClockConfig::fei().clocks();
+
+const FAILURE_NONE: u8 = 0;
+const FAILURE_OSCILLATOR: u8 = 1;
+const FAILURE_PLL_LOCK: u8 = 2;
+
+static FAILURE: AtomicU8 = AtomicU8::new(FAILURE_NONE);
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Ownership of the MCG, used up by [`ClockConfig::freeze`].
+pub struct Mcg {
+    _private: (),
+}
+
+impl Mcg {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Fei,
+    Fee {
+        crystal_hz: u32,
+    },
+    Pee {
+        crystal_hz: u32,
+        prdiv: u32,
+        vdiv: u32,
+    },
+    Blpi,
+    Blpe {
+        crystal_hz: u32,
+    },
+}
+
+/// Clock tree configuration, applied by [`Self::freeze`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockConfig {
+    mode: Mode,
+    max_core_hz: u32,
+}
+
+impl ClockConfig {
+    /// FLL engaged internal: the FLL multiplies the slow internal reference
+    /// to about 48 MHz. Needs no crystal.
+    #[must_use]
+    pub const fn fei() -> Self {
+        Self::new(Mode::Fei)
+    }
+
+    /// FLL engaged external: the FLL multiplies the crystal, divided down to
+    /// about 32 kHz, to between 40 and 50 MHz.
+    ///
+    /// # Panics
+    /// Panics if `crystal_hz` can't be divided into the FLL reference range.
+    #[must_use]
+    pub const fn fee(crystal_hz: u32) -> Self {
+        fll_reference(crystal_hz);
+        Self::new(Mode::Fee { crystal_hz })
+    }
+
+    /// PLL engaged external: the PLL multiplies the crystal to `pll_hz`,
+    /// between 48 and 100 MHz.
+    ///
+    /// # Panics
+    /// Panics if no PLL dividers give exactly `pll_hz` from `crystal_hz`.
+    #[must_use]
+    pub const fn pee(crystal_hz: u32, pll_hz: u32) -> Self {
+        fll_reference(crystal_hz);
+        let (prdiv, vdiv) = pll_dividers(crystal_hz, pll_hz);
+        Self::new(Mode::Pee {
+            crystal_hz,
+            prdiv,
+            vdiv,
+        })
+    }
+
+    /// Bypassed low power internal: the core runs from the 4 MHz fast
+    /// internal reference, with the FLL and PLL off.
+    #[must_use]
+    pub const fn blpi() -> Self {
+        Self::new(Mode::Blpi)
+    }
+
+    /// Bypassed low power external: the core runs from the crystal, with the
+    /// FLL and PLL off.
+    ///
+    /// # Panics
+    /// Panics if `crystal_hz` is outside the oscillator's ranges.
+    #[must_use]
+    pub const fn blpe(crystal_hz: u32) -> Self {
+        fll_reference(crystal_hz);
+        Self::new(Mode::Blpe { crystal_hz })
+    }
+
+    const fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            max_core_hz: MAX_CORE_HZ,
+        }
+    }
+
+    /// Divide the MCG output down to at most `max_core_hz` for the core.
+    /// Defaults to, and is limited to, 48 MHz.
+    #[must_use]
+    pub const fn core_hz(mut self, max_core_hz: u32) -> Self {
+        self.max_core_hz = max_core_hz;
+        self
+    }
+
+    /// The clocks this configuration produces, without applying it.
+    ///
+    /// # Panics
+    /// Panics if the core clock can't be divided down to the requested
+    /// maximum.
+    #[must_use]
+    pub const fn clocks(&self) -> Clocks {
+        let (mcgout_hz, peripheral_hz) = match self.mode {
+            Mode::Fei => {
+                let fll_hz = SLOW_IRC_HZ * FLL_FACTOR_DMX32;
+                (fll_hz, fll_hz)
+            }
+            Mode::Fee { crystal_hz } => {
+                let fll_hz = fll_output(crystal_hz);
+                (fll_hz, fll_hz)
+            }
+            Mode::Pee {
+                crystal_hz,
+                prdiv,
+                vdiv,
+            } => {
+                let pll_hz = crystal_hz / prdiv * vdiv;
+                (pll_hz, pll_hz / 2)
+            }
+            Mode::Blpi => (FAST_IRC_HZ, FAST_IRC_HZ),
+            Mode::Blpe { crystal_hz } => (crystal_hz, crystal_hz),
+        };
+
+        let max_core_hz = if self.max_core_hz < MAX_CORE_HZ {
+            self.max_core_hz
+        } else {
+            MAX_CORE_HZ
+        };
+
+        let core_divider = divider(mcgout_hz, max_core_hz, 16);
+        let core_hz = mcgout_hz / core_divider;
+        let bus_divider = divider(core_hz, MAX_BUS_HZ, 8);
+
+        Clocks {
+            mcgout_hz,
+            core_hz,
+            bus_hz: core_hz / bus_divider,
+            peripheral_hz,
+            core_divider,
+            bus_divider,
+        }
+    }
+
+    /// Switch the MCG to this configuration. The MCG must still be in its
+    /// reset mode, FEI.
+    ///
+    /// Modes using the crystal also enable the clock monitor, and PEE the
+    /// loss of lock interrupt. If either fires the MCG falls back to FEI, see
+    /// [`wait_for_failure`].
+    ///
+    /// # Errors
+    /// Returns the fallback clocks if the crystal didn't start or the PLL
+    /// didn't lock, after falling back to FEI. The failure is also reported
+    /// by [`failure`] and [`wait_for_failure`].
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Self::clocks`].
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn freeze(self, _mcg: Mcg) -> Result<Clocks, Failed> {
+        let clocks = self.clocks();
+
+        // Slow the core down before speeding the MCG up.
+        set_dividers(&clocks);
+
+        if let Err(error) = self.switch() {
+            fall_back();
+
+            let failure = match error {
+                Error::Oscillator => FAILURE_OSCILLATOR,
+                Error::PllLock => FAILURE_PLL_LOCK,
+            };
+            FAILURE.store(failure, Ordering::Relaxed);
+
+            return Err(Failed {
+                error,
+                clocks: FALLBACK,
+            });
+        }
+
+        set_peripheral_source(self.mode);
+        self.enable_monitor();
+
+        Ok(clocks)
+    }
+
+    fn switch(&self) -> Result<(), Error> {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        match self.mode {
+            Mode::Fei => {
+                peripherals
+                    .mcg
+                    .c4()
+                    .modify(|_, w| w.dmx32()._1().drst_drs()._01());
+
+                while !peripherals.mcg.s().read().clkst().is_00() {}
+            }
+            Mode::Fee { crystal_hz } => {
+                enable_oscillator(crystal_hz)?;
+
+                peripherals.mcg.c4().modify(|_, w| {
+                    w.dmx32()
+                        .bit(fll_reference(crystal_hz).1 == SLOW_IRC_HZ)
+                        .drst_drs()
+                        ._01()
+                });
+                peripherals.mcg.c1().write(|w| unsafe {
+                    w.clks()
+                        ._00()
+                        .frdiv()
+                        .bits(fll_reference(crystal_hz).0)
+                        .irefs()
+                        ._0()
+                });
+
+                wait(|s| s.irefst().bit_is_clear(), Error::Oscillator)?;
+                wait(|s| s.clkst().is_00(), Error::Oscillator)?;
+            }
+            Mode::Pee {
+                crystal_hz,
+                prdiv,
+                vdiv,
+            } => {
+                enter_fbe(crystal_hz)?;
+
+                // Dividers are range checked by the builder.
+                #[allow(clippy::cast_possible_truncation)]
+                {
+                    peripherals
+                        .mcg
+                        .c5()
+                        .write(|w| unsafe { w.prdiv0().bits((prdiv - 1) as u8) });
+                    peripherals
+                        .mcg
+                        .c6()
+                        .write(|w| unsafe { w.plls()._1().vdiv0().bits((vdiv - 24) as u8) });
+                }
+
+                wait(|s| s.pllst().bit_is_set(), Error::PllLock)?;
+                wait(|s| s.lock0().bit_is_set(), Error::PllLock)?;
+
+                peripherals.mcg.c1().modify(|_, w| w.clks()._00());
+
+                wait(|s| s.clkst().is_11(), Error::PllLock)?;
+            }
+            Mode::Blpi => {
+                peripherals.mcg.sc().modify(|_, w| w.fcrdiv()._000());
+                peripherals.mcg.c2().modify(|_, w| w.ircs()._1());
+                peripherals
+                    .mcg
+                    .c1()
+                    .write(|w| w.clks()._01().irefs()._1().irclken()._1());
+
+                while peripherals.mcg.s().read().ircst().bit_is_clear() {}
+                while !peripherals.mcg.s().read().clkst().is_01() {}
+
+                peripherals.mcg.c2().modify(|_, w| w.lp()._1());
+            }
+            Mode::Blpe { crystal_hz } => {
+                enter_fbe(crystal_hz)?;
+
+                peripherals.mcg.c2().modify(|_, w| w.lp()._1());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enable_monitor(&self) {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        match self.mode {
+            Mode::Fei | Mode::Blpi => return,
+            Mode::Fee { .. } | Mode::Blpe { .. } => {
+                peripherals.mcg.c2().modify(|_, w| w.locre0()._0());
+                peripherals.mcg.c6().modify(|_, w| w.cme0()._1());
+            }
+            Mode::Pee { .. } => {
+                peripherals.mcg.c2().modify(|_, w| w.locre0()._0());
+                peripherals.mcg.s().write(|w| w.lols0()._1());
+                peripherals
+                    .mcg
+                    .c6()
+                    .modify(|_, w| w.cme0()._1().lolie0()._1());
+            }
+        }
+
+        unsafe { cortex_m::peripheral::NVIC::unmask(pac::Interrupt::MCG) };
+    }
+}
+
+/// Possible clock errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Error {
+    /// The crystal oscillator didn't start, or stopped.
+    Oscillator,
+    /// The PLL didn't lock, or lost lock.
+    PllLock,
+}
+
+/// A clock failure, after falling back to FEI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Failed {
+    pub error: Error,
+    /// Clocks after falling back.
+    pub clocks: Clocks,
+}
+
+/// The clock failure detected by or since [`ClockConfig::freeze`], if any.
+#[must_use]
+pub fn failure() -> Option<Failed> {
+    let error = match FAILURE.load(Ordering::Relaxed) {
+        FAILURE_NONE => return None,
+        FAILURE_OSCILLATOR => Error::Oscillator,
+        FAILURE_PLL_LOCK => Error::PllLock,
+
+        _ => unreachable!(),
+    };
+
+    Some(Failed {
+        error,
+        clocks: FALLBACK,
+    })
+}
+
+/// Wait until the clock monitor detects a failure. Drivers set up with the
+/// previous [`Clocks`] run at the wrong rates from then on.
+pub async fn wait_for_failure() -> Failed {
+    core::future::poll_fn(|cx| {
+        WAKER.register(cx.waker());
+        failure().map_or(Poll::Pending, Poll::Ready)
+    })
+    .await
+}
+
+/// Clock frequencies after [`ClockConfig::freeze`]. Units of Hertz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Clocks {
+    mcgout_hz: u32,
+    core_hz: u32,
+    bus_hz: u32,
+    peripheral_hz: u32,
+    core_divider: u32,
+    bus_divider: u32,
+}
+
+impl Clocks {
+    /// MCG output, before the system dividers.
+    #[must_use]
+    pub const fn mcgout_hz(&self) -> u32 {
+        self.mcgout_hz
+    }
+
+    /// Core, system and `SysTick` clock. Also clocks SPI1 and DMA.
+    #[must_use]
+    pub const fn core_hz(&self) -> u32 {
+        self.core_hz
+    }
+
+    /// Bus and flash clock. Also clocks SPI0.
+    #[must_use]
+    pub const fn bus_hz(&self) -> u32 {
+        self.bus_hz
+    }
+
+    /// Clock selected for TPM and UART0.
+    #[must_use]
+    pub const fn peripheral_hz(&self) -> u32 {
+        self.peripheral_hz
+    }
+}
+
+/// Smallest divider from 1 to `limit` bringing `clock_hz` to at most
+/// `max_hz`.
+const fn divider(clock_hz: u32, max_hz: u32, limit: u32) -> u32 {
+    let divider = clock_hz.div_ceil(max_hz);
+    assert!(divider <= limit, "clock can't be divided down far enough");
+
+    if divider == 0 {
+        1
+    } else {
+        divider
+    }
+}
+
+/// FRDIV value and the resulting FLL reference for a crystal. The FLL needs
+/// a reference from 31.25 to 39.0625 kHz.
+const fn fll_reference(crystal_hz: u32) -> (u8, u32) {
+    const LOW_RANGE_DIVIDERS: [u32; 8] = [1, 2, 4, 8, 16, 32, 64, 128];
+    const HIGH_RANGE_DIVIDERS: [u32; 8] = [32, 64, 128, 256, 512, 1024, 1280, 1536];
+
+    let dividers = if oscillator_range(crystal_hz) == 0 {
+        LOW_RANGE_DIVIDERS
+    } else {
+        HIGH_RANGE_DIVIDERS
+    };
+
+    let mut frdiv = 0;
+    while frdiv < dividers.len() {
+        let reference_hz = crystal_hz / dividers[frdiv];
+        if reference_hz >= 31_250 && reference_hz <= 39_062 {
+            #[allow(clippy::cast_possible_truncation)]
+            return (frdiv as u8, reference_hz);
+        }
+        frdiv += 1;
+    }
+
+    panic!("crystal can't be divided into the FLL reference range");
+}
+
+const fn fll_output(crystal_hz: u32) -> u32 {
+    let (_, reference_hz) = fll_reference(crystal_hz);
+    if reference_hz == SLOW_IRC_HZ {
+        reference_hz * FLL_FACTOR_DMX32
+    } else {
+        reference_hz * FLL_FACTOR
+    }
+}
+
+/// RANGE0 value for a crystal.
+const fn oscillator_range(crystal_hz: u32) -> u8 {
+    match crystal_hz {
+        32_000..=40_000 => 0,
+        3_000_000..=8_000_000 => 1,
+        8_000_001..=32_000_000 => 2,
+
+        _ => panic!("crystal frequency outside the oscillator ranges"),
+    }
+}
+
+/// PRDIV and VDIV dividers, giving a PLL reference of 2 to 4 MHz and an
+/// output of exactly `pll_hz`.
+const fn pll_dividers(crystal_hz: u32, pll_hz: u32) -> (u32, u32) {
+    assert!(
+        pll_hz >= 48_000_000 && pll_hz <= 100_000_000,
+        "PLL output must be from 48 to 100 MHz"
+    );
+
+    let mut prdiv = 1;
+    while prdiv <= 25 {
+        let reference_hz = crystal_hz / prdiv;
+        if crystal_hz.is_multiple_of(prdiv)
+            && reference_hz >= 2_000_000
+            && reference_hz <= 4_000_000
+            && pll_hz.is_multiple_of(reference_hz)
+        {
+            let vdiv = pll_hz / reference_hz;
+            if vdiv >= 24 && vdiv <= 55 {
+                return (prdiv, vdiv);
+            }
+        }
+        prdiv += 1;
+    }
+
+    panic!("PLL output not reachable from crystal");
+}
+
+/// Shortest time allowed for the crystal to start or the PLL to lock, well
+/// above the few milliseconds either takes while inside the watchdog timeout
+/// running from reset. Units of microseconds.
+const TIMEOUT_US: u32 = 100_000;
+/// Core clock while switching, the FLL output at reset. Units of Hertz.
+const RESET_CORE_HZ: u32 = SLOW_IRC_HZ * 640;
+/// Fewest core cycles one spin of [`wait`] takes: reading the MCG status,
+/// testing it and branching.
+const CYCLES_PER_SPIN: u32 = 4;
+/// Spins giving at least [`TIMEOUT_US`] at the reset clock. Slower spins and
+/// the core divider set before switching make the wait longer, only the PLL
+/// lock wait, running from a crystal above 21 MHz, can be shorter.
+const TIMEOUT_SPINS: u32 = RESET_CORE_HZ.div_ceil(1_000_000) * TIMEOUT_US / CYCLES_PER_SPIN;
+
+/// Spin until `condition` holds on the MCG status, or fail with `error`.
+fn wait(condition: impl Fn(&pac::mcg::s::R) -> bool, error: Error) -> Result<(), Error> {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    for _ in 0..TIMEOUT_SPINS {
+        if condition(&peripherals.mcg.s().read()) {
+            return Ok(());
+        }
+    }
+
+    Err(error)
+}
+
+fn set_dividers(clocks: &Clocks) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    // Dividers are at most 16 and 8.
+    #[allow(clippy::cast_possible_truncation)]
+    peripherals.sim.clkdiv1().write(|w| unsafe {
+        w.outdiv1()
+            .bits((clocks.core_divider - 1) as u8)
+            .outdiv4()
+            .bits((clocks.bus_divider - 1) as u8)
+    });
+}
+
+/// Clock TPM and UART0 from the MCG output's source.
+fn set_peripheral_source(mode: Mode) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    peripherals.sim.sopt2().modify(|_, w| match mode {
+        Mode::Fei | Mode::Fee { .. } => w.tpmsrc()._01().uart0src()._01().pllfllsel()._0(),
+        Mode::Pee { .. } => w.tpmsrc()._01().uart0src()._01().pllfllsel()._1(),
+        Mode::Blpe { .. } => w.tpmsrc()._10().uart0src()._10(),
+        Mode::Blpi => w.tpmsrc()._11().uart0src()._11(),
+    });
+}
+
+fn enable_oscillator(crystal_hz: u32) -> Result<(), Error> {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    // OSCERCLK clocks TPM and UART0 in BLPE.
+    peripherals.osc0.cr().modify(|_, w| w.erclken()._1());
+
+    peripherals
+        .mcg
+        .c2()
+        .write(|w| unsafe { w.range0().bits(oscillator_range(crystal_hz)).erefs0()._1() });
+
+    wait(|s| s.oscinit0().bit_is_set(), Error::Oscillator)
+}
+
+/// Switch from FEI to FLL bypassed external, on the way to PEE or BLPE.
+fn enter_fbe(crystal_hz: u32) -> Result<(), Error> {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    enable_oscillator(crystal_hz)?;
+
+    peripherals.mcg.c1().write(|w| unsafe {
+        w.clks()
+            ._10()
+            .frdiv()
+            .bits(fll_reference(crystal_hz).0)
+            .irefs()
+            ._0()
+    });
+
+    wait(|s| s.irefst().bit_is_clear(), Error::Oscillator)?;
+    wait(|s| s.clkst().is_10(), Error::Oscillator)
+}
+
+/// Switch to FEI from any mode, without relying on the crystal or PLL.
+fn fall_back() {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    // Stop monitoring the clocks being dropped, and clear their flags.
+    peripherals
+        .mcg
+        .c6()
+        .modify(|_, w| w.cme0()._0().lolie0()._0());
+    peripherals.mcg.sc().modify(|_, w| w.locs0()._1());
+    peripherals.mcg.s().write(|w| w.lols0()._1());
+
+    // Run from the slow internal reference while the FLL is set up. A stuck
+    // switch is no worse than staying on a failed clock.
+    peripherals.mcg.c2().modify(|_, w| w.lp()._0().ircs()._0());
+    peripherals
+        .mcg
+        .c1()
+        .modify(|_, w| w.clks()._01().irefs()._1());
+    let _ = wait(|s| s.clkst().is_01(), Error::Oscillator);
+
+    set_dividers(&FALLBACK);
+
+    peripherals.mcg.c6().modify(|_, w| w.plls()._0());
+    peripherals
+        .mcg
+        .c4()
+        .modify(|_, w| w.dmx32()._1().drst_drs()._01());
+    peripherals.mcg.c1().modify(|_, w| w.clks()._00());
+
+    set_peripheral_source(Mode::Fei);
+}
+
+#[interrupt]
+fn MCG() {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    let failure = if peripherals.mcg.sc().read().locs0().bit_is_set() {
+        FAILURE_OSCILLATOR
+    } else {
+        FAILURE_PLL_LOCK
+    };
+
+    fall_back();
+
+    FAILURE.store(failure, Ordering::Relaxed);
+    WAKER.wake();
+}
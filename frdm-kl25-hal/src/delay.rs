@@ -0,0 +1,312 @@
+//! Delays and tickers counting a free-running TPM. Its overflow interrupt
+//! extends the 16-bit count, and its channel 0 compare interrupts at the
+//! earliest deadline being waited for, so waiting tasks are woken rather than
+//! polled.
+
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use core::task::Poll;
+
+use critical_section::{CriticalSection, Mutex};
+use embassy_sync::waitqueue::MultiWakerRegistration;
+
+use crate::clock::Clocks;
+use crate::tpm::Timer;
+
+/// Fastest rate the timer counts at, so it overflows at most once every
+/// 16 ms. Units of Hertz.
+const MAX_TICK_HZ: u32 = 4_000_000;
+
+/// Deadlines are only set on the compare channel once they are this close,
+/// so it can't match a lap early.
+const COMPARE_WINDOW: u64 = 0x8000;
+
+/// Index of the counting timer, `u8::MAX` until a [`Delay`] is created.
+static TIMER: AtomicU8 = AtomicU8::new(u8::MAX);
+/// Number of times the timer has overflowed.
+static WRAPS: AtomicU32 = AtomicU32::new(0);
+/// Rate the timer counts at, zero until a [`Delay`] is created. Units of
+/// Hertz.
+static TICK_HZ: AtomicU32 = AtomicU32::new(0);
+
+/// Earliest deadline being waited for, `u64::MAX` if none.
+static ALARM: Mutex<Cell<u64>> = Mutex::new(Cell::new(u64::MAX));
+/// Tasks woken once the alarm passes, each re-registering its own deadline.
+static WAKERS: Mutex<RefCell<MultiWakerRegistration<8>>> =
+    Mutex::new(RefCell::new(MultiWakerRegistration::new()));
+
+/// Timer ticks counted since the first [`Delay`] was created, or zero before.
+fn ticks() -> u64 {
+    let index = TIMER.load(Ordering::Relaxed);
+    if index == u8::MAX {
+        return 0;
+    }
+
+    loop {
+        let wraps = WRAPS.load(Ordering::Relaxed);
+        let mut count = counter(index);
+        let mut pending = 0;
+
+        // Overflowed, but the interrupt hasn't run yet, for example inside a
+        // critical section.
+        if overflow_pending(index) {
+            count = counter(index);
+            pending = 1;
+        }
+
+        if WRAPS.load(Ordering::Relaxed) == wraps {
+            return ((u64::from(wraps) + pending) << 16) | u64::from(count);
+        }
+    }
+}
+
+/// Time since the first [`Delay`] was created, or zero before. Units of
+/// microseconds.
+#[must_use]
+pub fn uptime_us() -> u64 {
+    match u64::from(TICK_HZ.load(Ordering::Relaxed)) {
+        0 => 0,
+        tick_hz => {
+            let ticks = ticks();
+            ticks / tick_hz * 1_000_000 + ticks % tick_hz * 1_000_000 / tick_hz
+        }
+    }
+}
+
+async fn wait_until(deadline: u64) {
+    core::future::poll_fn(|cx| {
+        critical_section::with(|cs| {
+            if ticks() >= deadline {
+                return Poll::Ready(());
+            }
+
+            WAKERS.borrow_ref_mut(cs).register(cx.waker());
+
+            let alarm = ALARM.borrow(cs);
+            if deadline < alarm.get() {
+                alarm.set(deadline);
+                update_alarm(cs);
+            }
+
+            Poll::Pending
+        })
+    })
+    .await;
+}
+
+/// Wake the waiting tasks if the alarm has passed, otherwise set the compare
+/// channel for it once it is close enough.
+fn update_alarm(cs: CriticalSection<'_>) {
+    let index = TIMER.load(Ordering::Relaxed);
+    let alarm = ALARM.borrow(cs);
+
+    loop {
+        let now = ticks();
+
+        if now >= alarm.get() {
+            alarm.set(u64::MAX);
+            set_compare(index, None);
+            WAKERS.borrow_ref_mut(cs).wake();
+            return;
+        }
+
+        if alarm.get() - now >= COMPARE_WINDOW {
+            // An overflow comes first, and sets the channel then.
+            set_compare(index, None);
+            return;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        set_compare(index, Some(alarm.get() as u16));
+
+        // CnV takes effect on the next count, so a deadline reached by then
+        // would only match a lap later. Spin out the last tick instead.
+        if ticks() + 1 < alarm.get() {
+            return;
+        }
+    }
+}
+
+fn on_interrupt() {
+    let index = TIMER.load(Ordering::Relaxed);
+
+    if crate::tpm::take_overflow(u32::from(index)) {
+        // Only load and store, thumbv6m has no compare and swap. This handler
+        // is the only writer and can't preempt itself.
+        WRAPS.store(
+            WRAPS.load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Relaxed,
+        );
+    }
+
+    // Also clears the channel flag.
+    critical_section::with(update_alarm);
+}
+
+fn counter(index: u8) -> u16 {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match index {
+        0 => peripherals.tpm0.cnt().read().count().bits(),
+        1 => peripherals.tpm1.cnt().read().count().bits(),
+        2 => peripherals.tpm2.cnt().read().count().bits(),
+
+        _ => unreachable!(),
+    }
+}
+
+fn overflow_pending(index: u8) -> bool {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match index {
+        0 => peripherals.tpm0.sc().read().tof().bit_is_set(),
+        1 => peripherals.tpm1.sc().read().tof().bit_is_set(),
+        2 => peripherals.tpm2.sc().read().tof().bit_is_set(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Interrupt when the counter reaches `value`, or never if `None`. Clears
+/// any earlier match either way.
+fn set_compare(index: u8, value: Option<u16>) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    // Software output compare. CHF is cleared by writing one, and left alone
+    // by writing zero, so a match after it is cleared still interrupts.
+    match index {
+        0 => peripherals
+            .tpm0
+            .c0sc()
+            .write(|w| w.msa().set_bit().chf()._1().chie()._0()),
+        1 => peripherals
+            .tpm1
+            .c0sc()
+            .write(|w| w.msa().set_bit().chf()._1().chie()._0()),
+        2 => peripherals
+            .tpm2
+            .c0sc()
+            .write(|w| w.msa().set_bit().chf()._1().chie()._0()),
+
+        _ => unreachable!(),
+    }
+
+    let Some(value) = value else { return };
+
+    unsafe {
+        match index {
+            0 => peripherals.tpm0.c0v().write(|w| w.val().bits(value)),
+            1 => peripherals.tpm1.c0v().write(|w| w.val().bits(value)),
+            2 => peripherals.tpm2.c0v().write(|w| w.val().bits(value)),
+
+            _ => unreachable!(),
+        }
+    }
+
+    match index {
+        0 => peripherals
+            .tpm0
+            .c0sc()
+            .write(|w| w.msa().set_bit().chie()._1()),
+        1 => peripherals
+            .tpm1
+            .c0sc()
+            .write(|w| w.msa().set_bit().chie()._1()),
+        2 => peripherals
+            .tpm2
+            .c0sc()
+            .write(|w| w.msa().set_bit().chie()._1()),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Async delays, counting a timer clocked from the peripheral clock. Copies
+/// share the one timer.
+///
+/// The rate is taken from the [`Clocks`] given to [`Delay::new`], so delays
+/// stretch if the clocks later fall back after a failure.
+#[derive(Debug, Clone, Copy)]
+pub struct Delay {
+    tick_hz: u32,
+}
+
+impl Delay {
+    /// Start `timer` counting, at the peripheral clock divided down to at
+    /// most 4 MHz. The timer is used for as long as the program runs.
+    #[must_use]
+    pub fn new<T: Timer>(timer: T, clocks: &Clocks) -> Self {
+        let prescale = (0..=7)
+            .find(|prescale| clocks.peripheral_hz() >> prescale <= MAX_TICK_HZ)
+            .unwrap_or(7);
+        let tick_hz = clocks.peripheral_hz() >> prescale;
+
+        crate::tpm::ensure_clock_active::<T>();
+        crate::tpm::enable_timer::<T>(false);
+        crate::tpm::set_timer_mod_value::<T>(u16::MAX);
+
+        #[allow(clippy::cast_possible_truncation)]
+        let index = T::INDEX as u8;
+
+        TIMER.store(index, Ordering::Relaxed);
+        WRAPS.store(0, Ordering::Relaxed);
+        TICK_HZ.store(tick_hz, Ordering::Relaxed);
+
+        set_compare(index, None);
+        crate::tpm::set_interrupt_handler::<T>(Some(on_interrupt));
+        crate::tpm::start_with_overflow_interrupt::<T>(prescale);
+
+        // Never handed back, so nothing else can reconfigure it.
+        drop(timer);
+
+        Self { tick_hz }
+    }
+
+    /// Ticker firing every `period_us`, starting one period from now. Ticks
+    /// are scheduled from the previous tick rather than when it was awaited,
+    /// so they don't drift.
+    #[must_use]
+    pub fn every_us(self, period_us: u32) -> Ticker {
+        let period = self.ticks_of(u64::from(period_us), 1_000_000);
+
+        Ticker {
+            period,
+            next: ticks() + period,
+        }
+    }
+
+    /// Ticks in `time` units of `1 / per_second` seconds, rounded up.
+    fn ticks_of(self, time: u64, per_second: u64) -> u64 {
+        (time * u64::from(self.tick_hz)).div_ceil(per_second)
+    }
+}
+
+impl embedded_hal_async::delay::DelayNs for Delay {
+    async fn delay_ns(&mut self, ns: u32) {
+        wait_until(ticks() + self.ticks_of(u64::from(ns), 1_000_000_000)).await;
+    }
+}
+
+/// Spins on the count, for short waits where nothing can be awaited, such as
+/// in interrupt handlers. It keeps counting with interrupts masked.
+impl embedded_hal::delay::DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        let deadline = ticks() + self.ticks_of(u64::from(ns), 1_000_000_000);
+        while ticks() < deadline {}
+    }
+}
+
+/// Fires at a fixed rate, see [`Delay::every_us`].
+#[derive(Debug)]
+pub struct Ticker {
+    period: u64,
+    next: u64,
+}
+
+impl Ticker {
+    /// Wait for the next tick. Returns immediately if it has already passed.
+    pub async fn next(&mut self) {
+        wait_until(self.next).await;
+        self.next += self.period;
+    }
+}
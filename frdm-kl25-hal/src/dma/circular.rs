@@ -0,0 +1,187 @@
+//! Continuous reception into a ring buffer. The destination address wraps
+//! within the buffer using DMOD, and the channel interrupt restarts the count
+//! each lap, so a stream is never missed while it is being consumed.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use super::{
+    Config, Dma, Endpoint, Error, Linking, Peripheral, Request, Trigger, Word, WriteBuffer,
+};
+use super::{LAPS, RELOAD, STATUS};
+
+/// Receives words from a peripheral into a ring buffer, tracking a producer
+/// index moved by the DMA controller and a consumer index moved by reads.
+pub struct CircularReceiver<D: Dma, W, T> {
+    parts: Option<(D, Peripheral<W>, T)>,
+    buffer: *const W,
+    /// Buffer length in words.
+    len: u32,
+    /// Words read so far, wrapping.
+    consumed: u32,
+}
+
+impl<D, W, T> CircularReceiver<D, W, T>
+where
+    D: Dma,
+    W: Word,
+    T: WriteBuffer<Word = W>,
+{
+    /// Start receiving a word from `source` into `buffer` each time `request`
+    /// is asserted, wrapping back to the start of the buffer once it is full.
+    ///
+    /// # Panics
+    /// Panics if the buffer is not a power of two between 16 bytes and 256 KiB
+    /// long, or is not aligned to its own length.
+    pub fn new(channel: D, source: Peripheral<W>, mut buffer: T, request: Request) -> Self {
+        let (ptr, len) = unsafe { buffer.write_buffer() };
+        let bytes = len * W::SIZE.bytes();
+
+        assert!(
+            bytes.is_power_of_two() && (16..=0x4_0000).contains(&bytes),
+            "circular buffer must be a power of two between 16 bytes and 256 KiB"
+        );
+        assert!(
+            (ptr as usize).is_multiple_of(bytes),
+            "circular buffer must be aligned to its length"
+        );
+
+        let mut dest = Endpoint::memory(ptr.cast_const());
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            dest.modulo = bytes.trailing_zeros() as u8 - 3; // 16 bytes is 1.
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        RELOAD[D::INDEX as usize].store(bytes as u32, Ordering::Release);
+        LAPS[D::INDEX as usize].store(0, Ordering::Release);
+
+        super::set_request::<D>(Some(request));
+
+        compiler_fence(Ordering::SeqCst);
+
+        unsafe {
+            super::setup_dma_transfer::<D>(&Config {
+                source: source.endpoint(),
+                dest,
+                bytes,
+                trigger: Trigger::Request,
+                circular: true,
+                link: Linking::NONE,
+            });
+
+            cortex_m::peripheral::NVIC::unmask(super::interrupt_of::<D>());
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        Self {
+            parts: Some((channel, source, buffer)),
+            buffer: ptr.cast_const(),
+            len: len as u32,
+            consumed: 0,
+        }
+    }
+
+    /// Index of the next word the controller will write.
+    pub fn producer(&self) -> usize {
+        (self.produced() % self.len) as usize
+    }
+
+    /// Index of the next word to be read.
+    pub fn consumer(&self) -> usize {
+        (self.consumed % self.len) as usize
+    }
+
+    /// Number of words waiting to be read.
+    ///
+    /// # Errors
+    /// Returns [`Error::Overrun`] if unread words have been overwritten, or an
+    /// error if the controller stopped reception.
+    pub fn available(&self) -> Result<usize, Error> {
+        Ok(self.waiting()? as usize)
+    }
+
+    /// Copy as many waiting words as fit into `words`, returning how many were
+    /// copied.
+    ///
+    /// # Errors
+    /// Returns [`Error::Overrun`] if unread words were overwritten before or
+    /// while they were copied, or an error if the controller stopped
+    /// reception. Nothing is consumed, use [`CircularReceiver::clear`] to
+    /// resynchronise after an overrun.
+    pub fn read(&mut self, words: &mut [W]) -> Result<usize, Error> {
+        let count = words.len().min(self.waiting()? as usize);
+
+        compiler_fence(Ordering::SeqCst);
+
+        for (i, word) in words[..count].iter_mut().enumerate() {
+            #[allow(clippy::cast_possible_truncation)]
+            let index = self.consumed.wrapping_add(i as u32) % self.len;
+            *word = unsafe { self.buffer.add(index as usize).read_volatile() };
+        }
+
+        compiler_fence(Ordering::SeqCst);
+
+        // The controller may have lapped the copy.
+        self.waiting()?;
+
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            self.consumed = self.consumed.wrapping_add(count as u32);
+        }
+
+        Ok(count)
+    }
+
+    /// Discard every waiting word, including after an overrun.
+    pub fn clear(&mut self) {
+        self.consumed = self.produced();
+    }
+
+    /// Stop receiving, then release the channel and both ends.
+    pub fn release(mut self) -> (D, Peripheral<W>, T) {
+        super::abort_dma_transfer::<D>();
+
+        compiler_fence(Ordering::SeqCst);
+
+        let Some(parts) = self.parts.take() else { unreachable!() };
+        parts
+    }
+
+    fn waiting(&self) -> Result<u32, Error> {
+        Error::from_status(STATUS[D::INDEX as usize].load(Ordering::Acquire))?;
+
+        let waiting = self.produced().wrapping_sub(self.consumed);
+        if waiting > self.len {
+            return Err(Error::Overrun);
+        }
+
+        Ok(waiting)
+    }
+
+    /// Words written so far, wrapping.
+    #[allow(clippy::cast_possible_truncation)]
+    fn produced(&self) -> u32 {
+        let bytes = self.len * W::SIZE.bytes() as u32;
+
+        // A lap may end between the two reads.
+        loop {
+            let laps = LAPS[D::INDEX as usize].load(Ordering::Acquire);
+            let remaining = super::remaining_bytes::<D>();
+
+            if LAPS[D::INDEX as usize].load(Ordering::Acquire) == laps {
+                let written = (bytes - remaining) / W::SIZE.bytes() as u32;
+                return laps.wrapping_mul(self.len).wrapping_add(written);
+            }
+        }
+    }
+}
+
+impl<D: Dma, W, T> Drop for CircularReceiver<D, W, T> {
+    fn drop(&mut self) {
+        // Still running, the ends must not be released to anything else.
+        if self.parts.is_some() {
+            super::abort_dma_transfer::<D>();
+            compiler_fence(Ordering::SeqCst);
+        }
+    }
+}
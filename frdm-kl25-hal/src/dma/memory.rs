@@ -0,0 +1,224 @@
+//! Memory copy and fill helpers. The bulk of the buffer is moved in the
+//! widest words both ends are aligned for, and the few bytes either side of
+//! it by the core.
+//!
+//! Like [`Dma::memory_to_memory`], the helpers own the channel and buffers
+//! until they return them. Leaking the future then leaks the buffers too, so
+//! the controller can never write to memory that has been reused.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use super::{
+    Config, Dma, Endpoint, Error, Failed, Linking, ReadBuffer, Size, Trigger, WriteBuffer,
+    MAX_BYTES,
+};
+
+/// Copy `source` into `dest` using `channel`, then release all three.
+///
+/// # Errors
+/// Returns an error, along with the channel and both buffers, if the
+/// controller stopped the transfer.
+///
+/// # Panics
+/// Panics if the two buffers have different lengths.
+pub async fn dma_copy<D, S, T>(
+    mut channel: D,
+    source: S,
+    mut dest: T,
+) -> Result<(D, S, T), Failed<D, S, T>>
+where
+    D: Dma,
+    S: ReadBuffer<Word = u8>,
+    T: WriteBuffer<Word = u8>,
+{
+    let (source_ptr, source_len) = unsafe { source.read_buffer() };
+    let (dest_ptr, dest_len) = unsafe { dest.write_buffer() };
+    assert_eq!(dest_len, source_len, "DMA copy lengths differ");
+
+    // Both buffers are owned here until returned.
+    let result = unsafe {
+        copy(
+            &mut channel,
+            core::slice::from_raw_parts_mut(dest_ptr, dest_len),
+            core::slice::from_raw_parts(source_ptr, source_len),
+        )
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok((channel, source, dest)),
+        Err(error) => Err(Failed {
+            error,
+            channel,
+            source,
+            dest,
+        }),
+    }
+}
+
+/// Set every byte of `dest` to `value` using `channel`, then release both.
+///
+/// # Errors
+/// Returns an error, along with the channel and buffer, if the controller
+/// stopped the transfer. The source of [`Failed`] is the fill value.
+pub async fn dma_fill<D, T>(
+    mut channel: D,
+    mut dest: T,
+    value: u8,
+) -> Result<(D, T), Failed<D, u8, T>>
+where
+    D: Dma,
+    T: WriteBuffer<Word = u8>,
+{
+    let (dest_ptr, dest_len) = unsafe { dest.write_buffer() };
+
+    // The buffer is owned here until returned.
+    let result = unsafe {
+        fill(
+            &mut channel,
+            core::slice::from_raw_parts_mut(dest_ptr, dest_len),
+            value,
+        )
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok((channel, dest)),
+        Err(error) => Err(Failed {
+            error,
+            channel,
+            source: value,
+            dest,
+        }),
+    }
+}
+
+/// # Safety
+/// The returned future must be run to completion or dropped while `dest` is
+/// still valid, as it is when owned by the caller's future.
+async unsafe fn copy<D: Dma>(channel: &mut D, dest: &mut [u8], source: &[u8]) -> Result<(), Error> {
+    let source_addr = source.as_ptr() as usize;
+    let dest_addr = dest.as_ptr() as usize;
+
+    // Both ends must reach word alignment at the same offset.
+    let size = [Size::Bits32, Size::Bits16]
+        .into_iter()
+        .find(|size| {
+            dest_addr
+                .wrapping_sub(source_addr)
+                .is_multiple_of(size.bytes())
+        })
+        .unwrap_or(Size::Bits8);
+
+    let (head, body) = split(source_addr, source.len(), size);
+
+    dest[..head].copy_from_slice(&source[..head]);
+
+    for (offset, bytes) in chunks(head, body, size) {
+        let config = Config {
+            source: Endpoint {
+                address: source[offset..].as_ptr() as u32,
+                size,
+                increment: true,
+                modulo: 0,
+            },
+            dest: Endpoint {
+                address: dest[offset..].as_ptr() as u32,
+                size,
+                increment: true,
+                modulo: 0,
+            },
+            bytes,
+            trigger: Trigger::Start,
+            circular: false,
+            link: Linking::NONE,
+        };
+
+        run(channel, &config).await?;
+    }
+
+    dest[head + body..].copy_from_slice(&source[head + body..]);
+
+    Ok(())
+}
+
+/// # Safety
+/// As for [`copy`]. The fill pattern is read from the future itself, which
+/// stays valid once polled until it is dropped.
+async unsafe fn fill<D: Dma>(channel: &mut D, dest: &mut [u8], value: u8) -> Result<(), Error> {
+    let pattern = u32::from_ne_bytes([value; 4]);
+
+    let (head, body) = split(dest.as_ptr() as usize, dest.len(), Size::Bits32);
+
+    dest[..head].fill(value);
+
+    for (offset, bytes) in chunks(head, body, Size::Bits32) {
+        let config = Config {
+            source: Endpoint::peripheral(&raw const pattern),
+            dest: Endpoint {
+                address: dest[offset..].as_ptr() as u32,
+                size: Size::Bits32,
+                increment: true,
+                modulo: 0,
+            },
+            bytes,
+            trigger: Trigger::Start,
+            circular: false,
+            link: Linking::NONE,
+        };
+
+        run(channel, &config).await?;
+    }
+
+    dest[head + body..].fill(value);
+
+    Ok(())
+}
+
+/// Split `len` bytes at `addr` into a head up to the first `size` aligned
+/// address, and a body of whole `size` words.
+fn split(addr: usize, len: usize, size: Size) -> (usize, usize) {
+    let head = (addr.wrapping_neg() % size.bytes()).min(len);
+    let body = (len - head) / size.bytes() * size.bytes();
+    (head, body)
+}
+
+/// Offsets and lengths of transfers covering `body` bytes from `start`.
+fn chunks(start: usize, body: usize, size: Size) -> impl Iterator<Item = (usize, usize)> {
+    let max = MAX_BYTES / size.bytes() * size.bytes();
+    (start..start + body)
+        .step_by(max)
+        .map(move |offset| (offset, max.min(start + body - offset)))
+}
+
+async fn run<D: Dma>(_channel: &mut D, config: &Config) -> Result<(), Error> {
+    super::set_request::<D>(None);
+
+    let _guard = Abort::<D> {
+        _marker: PhantomData,
+    };
+
+    compiler_fence(Ordering::SeqCst);
+
+    let result = unsafe {
+        super::setup_dma_transfer::<D>(config);
+        super::wait_dma_transfer::<D>().await
+    };
+
+    compiler_fence(Ordering::SeqCst);
+
+    result
+}
+
+/// Stops the transfer when dropped, so a cancelled copy or fill no longer
+/// accesses the buffers once their borrow ends.
+struct Abort<D: Dma> {
+    _marker: PhantomData<D>,
+}
+
+impl<D: Dma> Drop for Abort<D> {
+    fn drop(&mut self) {
+        super::abort_dma_transfer::<D>();
+    }
+}
@@ -0,0 +1,963 @@
+#![allow(clippy::module_name_repetitions)]
+
+pub mod circular;
+mod memory;
+
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, AtomicU32, AtomicU8, Ordering};
+
+use cortex_m_rt::interrupt;
+use embassy_sync::waitqueue::AtomicWaker;
+pub use embedded_dma::{ReadBuffer, WriteBuffer};
+pub use memory::{dma_copy, dma_fill};
+use pac::Interrupt as interrupt;
+use woven::{Either, Race};
+
+static WAKERS: [AtomicWaker; 4] = [const { AtomicWaker::new() }; 4];
+/// Top byte of `DSR_BCR` as captured by the interrupt, zero while running.
+static STATUS: [AtomicU8; 4] = [const { AtomicU8::new(0) }; 4];
+
+/// Byte count to restart a circular transfer with, zero if not circular.
+static RELOAD: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+/// Number of times a circular transfer has been restarted.
+static LAPS: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+
+const STATUS_BED: u8 = 1 << 4;
+const STATUS_BES: u8 = 1 << 5;
+const STATUS_CE: u8 = 1 << 6;
+
+/// Largest number of bytes a single transfer can move.
+pub const MAX_BYTES: usize = 0x000F_FFFF;
+
+pub trait Dma: sealed::Sealed {
+    const INDEX: u32;
+
+    /// Copy `source` into `dest`. Only as many words as fit in the shorter of
+    /// the two buffers are copied.
+    fn memory_to_memory<S, T, W>(
+        self,
+        source: S,
+        mut dest: T,
+    ) -> TransferBuilder<'static, Self, S, T>
+    where
+        Self: Sized,
+        S: ReadBuffer<Word = W>,
+        T: WriteBuffer<Word = W>,
+        W: Word,
+    {
+        let (source_ptr, source_len) = unsafe { source.read_buffer() };
+        let (dest_ptr, dest_len) = unsafe { dest.write_buffer() };
+
+        let config = Config {
+            source: Endpoint::memory(source_ptr),
+            dest: Endpoint::memory(dest_ptr),
+            bytes: source_len.min(dest_len) * W::SIZE.bytes(),
+            trigger: Trigger::Start,
+            circular: false,
+            link: Linking::NONE,
+        };
+
+        TransferBuilder::new(self, source, dest, config)
+    }
+
+    /// Write every word of `source` to a peripheral register.
+    fn memory_to_peripheral<S, W>(
+        self,
+        source: S,
+        dest: Peripheral<W>,
+    ) -> TransferBuilder<'static, Self, S, Peripheral<W>>
+    where
+        Self: Sized,
+        S: ReadBuffer<Word = W>,
+        W: Word,
+    {
+        let (source_ptr, source_len) = unsafe { source.read_buffer() };
+
+        let config = Config {
+            source: Endpoint::memory(source_ptr),
+            dest: dest.endpoint(),
+            bytes: source_len * W::SIZE.bytes(),
+            trigger: Trigger::Start,
+            circular: false,
+            link: Linking::NONE,
+        };
+
+        TransferBuilder::new(self, source, dest, config)
+    }
+
+    /// Fill `dest` with words read from a peripheral register.
+    fn peripheral_to_memory<T, W>(
+        self,
+        source: Peripheral<W>,
+        mut dest: T,
+    ) -> TransferBuilder<'static, Self, Peripheral<W>, T>
+    where
+        Self: Sized,
+        T: WriteBuffer<Word = W>,
+        W: Word,
+    {
+        let (dest_ptr, dest_len) = unsafe { dest.write_buffer() };
+
+        let config = Config {
+            source: source.endpoint(),
+            dest: Endpoint::memory(dest_ptr),
+            bytes: dest_len * W::SIZE.bytes(),
+            trigger: Trigger::Start,
+            circular: false,
+            link: Linking::NONE,
+        };
+
+        TransferBuilder::new(self, source, dest, config)
+    }
+}
+
+pub struct Dma0 {
+    _private: (),
+}
+
+impl Dma0 {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Dma for Dma0 {
+    const INDEX: u32 = 0;
+}
+
+pub struct Dma1 {
+    _private: (),
+}
+
+impl Dma1 {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Dma for Dma1 {
+    const INDEX: u32 = 1;
+}
+
+pub struct Dma2 {
+    _private: (),
+}
+
+impl Dma2 {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Dma for Dma2 {
+    const INDEX: u32 = 2;
+}
+
+pub struct Dma3 {
+    _private: (),
+}
+
+impl Dma3 {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Dma for Dma3 {
+    const INDEX: u32 = 3;
+}
+
+/// Width of a single DMA read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Size {
+    Bits8,
+    Bits16,
+    Bits32,
+}
+
+impl Size {
+    const fn bytes(self) -> usize {
+        match self {
+            Self::Bits8 => 1,
+            Self::Bits16 => 2,
+            Self::Bits32 => 4,
+        }
+    }
+
+    const fn bits(self) -> u8 {
+        match self {
+            Self::Bits8 => 0b01,
+            Self::Bits16 => 0b10,
+            Self::Bits32 => 0b00,
+        }
+    }
+}
+
+/// Word types the DMA controller can move.
+pub trait Word: sealed::Sealed + Copy {
+    const SIZE: Size;
+}
+
+impl Word for u8 {
+    const SIZE: Size = Size::Bits8;
+}
+
+impl Word for u16 {
+    const SIZE: Size = Size::Bits16;
+}
+
+impl Word for u32 {
+    const SIZE: Size = Size::Bits32;
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+
+    impl Sealed for super::Dma0 {}
+    impl Sealed for super::Dma1 {}
+    impl Sealed for super::Dma2 {}
+    impl Sealed for super::Dma3 {}
+}
+
+/// A fixed peripheral register, read or written without incrementing the
+/// address.
+pub struct Peripheral<W> {
+    address: *mut W,
+}
+
+impl<W: Word> Peripheral<W> {
+    /// # Safety
+    /// `address` must be a peripheral register that is valid to access with
+    /// words of type `W` for as long as any transfer using it is running.
+    #[must_use]
+    pub const unsafe fn new(address: *mut W) -> Self {
+        Self { address }
+    }
+
+    fn endpoint(&self) -> Endpoint {
+        Endpoint::peripheral(self.address)
+    }
+}
+
+/// DMAMUX request sources that can pace a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum Request {
+    Uart0Rx = 2,
+    Uart0Tx = 3,
+    Uart1Rx = 4,
+    Uart1Tx = 5,
+    Uart2Rx = 6,
+    Uart2Tx = 7,
+    Spi0Rx = 16,
+    Spi0Tx = 17,
+    Spi1Rx = 18,
+    Spi1Tx = 19,
+    I2c0 = 22,
+    I2c1 = 23,
+    Tpm0Channel0 = 24,
+    Tpm0Channel1 = 25,
+    Tpm0Channel2 = 26,
+    Tpm0Channel3 = 27,
+    Tpm0Channel4 = 28,
+    Tpm0Channel5 = 29,
+    Tpm1Channel0 = 32,
+    Tpm1Channel1 = 33,
+    Tpm2Channel0 = 34,
+    Tpm2Channel1 = 35,
+    Adc0 = 40,
+    Cmp0 = 42,
+    Dac0 = 45,
+    PortA = 49,
+    PortD = 52,
+    Tpm0Overflow = 54,
+    Tpm1Overflow = 55,
+    Tpm2Overflow = 56,
+    Tsi0 = 57,
+    AlwaysOn60 = 60,
+    AlwaysOn61 = 61,
+    AlwaysOn62 = 62,
+    AlwaysOn63 = 63,
+}
+
+/// When a linked channel is started by the channel linking to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Link {
+    /// After every word this channel moves.
+    EachWord,
+    /// Once, after this channel's transfer completes.
+    Completion,
+}
+
+/// A configured transfer that has not been started yet. Without a
+/// [`Request`] or link, the transfer runs as fast as the bus allows once
+/// started.
+///
+/// `'l` is how long the transfers it links to are borrowed for, so they can't
+/// be dropped or their channels reused while this one can still start them.
+pub struct TransferBuilder<'l, D, S, T> {
+    channel: D,
+    source: S,
+    dest: T,
+    config: Config,
+    request: Option<Request>,
+    _links: PhantomData<&'l ()>,
+}
+
+impl<D: Dma, S, T> TransferBuilder<'static, D, S, T> {
+    fn new(channel: D, source: S, dest: T, config: Config) -> Self {
+        Self {
+            channel,
+            source,
+            dest,
+            config,
+            request: None,
+            _links: PhantomData,
+        }
+    }
+}
+
+impl<'l, D: Dma, S, T> TransferBuilder<'l, D, S, T> {
+    /// Move one word each time `request` is asserted.
+    #[must_use]
+    pub fn request(mut self, request: Request) -> Self {
+        self.request = Some(request);
+        self.config.trigger = Trigger::Request;
+        self
+    }
+
+    /// Move one word each time another channel links to this one, instead of
+    /// starting immediately. See [`TransferBuilder::link`].
+    #[must_use]
+    pub fn on_link(mut self) -> Self {
+        self.request = None;
+        self.config.trigger = Trigger::Link;
+        self
+    }
+
+    /// Start the channel of `_to`, which should have been built with
+    /// [`TransferBuilder::on_link`], at each `when`. `_to` stays borrowed
+    /// until this transfer completes or is dropped.
+    #[must_use]
+    pub fn link<'a, L: Dma, S2, T2>(
+        self,
+        when: Link,
+        _to: &'a Transfer<'_, L, S2, T2>,
+    ) -> TransferBuilder<'a, D, S, T>
+    where
+        'l: 'a,
+    {
+        #[allow(clippy::cast_possible_truncation)]
+        let channel = L::INDEX as u8;
+
+        let mut this = self.relink();
+        this.config.link = match when {
+            Link::EachWord => Linking {
+                mode: 0b10,
+                first: channel,
+                second: 0,
+            },
+            Link::Completion => Linking {
+                mode: 0b11,
+                first: channel,
+                second: 0,
+            },
+        };
+        this
+    }
+
+    /// Start the channel of `_each_word` after every word this channel moves,
+    /// and the channel of `_completion` once this channel's transfer
+    /// completes. Both should have been built with
+    /// [`TransferBuilder::on_link`], and stay borrowed until this transfer
+    /// completes or is dropped.
+    #[must_use]
+    pub fn link_both<'a, L1: Dma, S1, T1, L2: Dma, S2, T2>(
+        self,
+        _each_word: &'a Transfer<'_, L1, S1, T1>,
+        _completion: &'a Transfer<'_, L2, S2, T2>,
+    ) -> TransferBuilder<'a, D, S, T>
+    where
+        'l: 'a,
+    {
+        let mut this = self.relink();
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            this.config.link = Linking {
+                mode: 0b01,
+                first: L1::INDEX as u8,
+                second: L2::INDEX as u8,
+            };
+        }
+        this
+    }
+
+    /// The same builder, borrowing its links for a shorter time.
+    fn relink<'a>(self) -> TransferBuilder<'a, D, S, T>
+    where
+        'l: 'a,
+    {
+        TransferBuilder {
+            channel: self.channel,
+            source: self.source,
+            dest: self.dest,
+            config: self.config,
+            request: self.request,
+            _links: PhantomData,
+        }
+    }
+
+    /// Start the transfer.
+    ///
+    /// # Panics
+    /// Panics if the transfer is longer than [`MAX_BYTES`].
+    pub fn start(self) -> Transfer<'l, D, S, T> {
+        assert!(self.config.bytes <= MAX_BYTES, "DMA transfer too long");
+
+        set_request::<D>(self.request);
+
+        compiler_fence(Ordering::SeqCst);
+
+        unsafe { setup_dma_transfer::<D>(&self.config) };
+
+        Transfer {
+            parts: Some((self.channel, self.source, self.dest)),
+            _links: PhantomData,
+        }
+    }
+}
+
+/// Possible transfer errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Error {
+    /// The transfer did not complete in time, and was aborted.
+    Timeout,
+    /// The channel was configured with an invalid address, size or count.
+    Configuration,
+    /// A bus error occurred reading from the source.
+    SourceBus,
+    /// A bus error occurred writing to the destination.
+    DestinationBus,
+    /// A circular buffer was overwritten before being read.
+    Overrun,
+}
+
+impl Error {
+    fn from_status(status: u8) -> Result<(), Self> {
+        if status & STATUS_CE != 0 {
+            Err(Self::Configuration)
+        } else if status & STATUS_BES != 0 {
+            Err(Self::SourceBus)
+        } else if status & STATUS_BED != 0 {
+            Err(Self::DestinationBus)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A transfer that did not complete, along with the channel and both ends.
+pub struct Failed<D, S, T> {
+    pub error: Error,
+    pub channel: D,
+    pub source: S,
+    pub dest: T,
+}
+
+/// A running transfer, which owns the channel and both ends until it
+/// completes. Dropping it aborts the transfer. `'l` is how long the transfers
+/// it links to are borrowed for, see [`TransferBuilder`].
+pub struct Transfer<'l, D: Dma, S, T> {
+    parts: Option<(D, S, T)>,
+    _links: PhantomData<&'l ()>,
+}
+
+impl<D: Dma, S, T> Transfer<'_, D, S, T> {
+    /// Wait for the transfer to complete, then release the channel and both
+    /// ends.
+    ///
+    /// # Errors
+    /// Returns an error if the controller stopped the transfer.
+    pub async fn wait(mut self) -> Result<(D, S, T), Failed<D, S, T>> {
+        let result = unsafe { wait_dma_transfer::<D>().await };
+
+        self.finish(result)
+    }
+
+    /// Wait up to `timeout_us` microseconds for the transfer to complete,
+    /// then release the channel and both ends.
+    ///
+    /// # Errors
+    /// Returns [`Error::Timeout`] if the transfer had to be aborted, or an
+    /// error if the controller stopped the transfer.
+    pub async fn wait_timeout<DEL>(
+        mut self,
+        delay: &mut DEL,
+        timeout_us: u32,
+    ) -> Result<(D, S, T), Failed<D, S, T>>
+    where
+        DEL: embedded_hal_async::delay::DelayNs,
+    {
+        let result = (
+            unsafe { wait_dma_transfer::<D>() },
+            delay.delay_us(timeout_us),
+        )
+            .race()
+            .await;
+
+        match result {
+            Either::First(result) => self.finish(result),
+            Either::Second(()) => {
+                abort_dma_transfer::<D>();
+                self.finish(Err(Error::Timeout))
+            }
+        }
+    }
+
+    fn finish(&mut self, result: Result<(), Error>) -> Result<(D, S, T), Failed<D, S, T>> {
+        let (channel, source, dest) = self.release();
+
+        match result {
+            Ok(()) => Ok((channel, source, dest)),
+            Err(error) => Err(Failed {
+                error,
+                channel,
+                source,
+                dest,
+            }),
+        }
+    }
+
+    fn release(&mut self) -> (D, S, T) {
+        compiler_fence(Ordering::SeqCst);
+
+        let Some(parts) = self.parts.take() else { unreachable!() };
+        parts
+    }
+}
+
+impl<D: Dma, S, T> Drop for Transfer<'_, D, S, T> {
+    fn drop(&mut self) {
+        // Still running, the ends must not be released to anything else.
+        if self.parts.is_some() {
+            abort_dma_transfer::<D>();
+            compiler_fence(Ordering::SeqCst);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Endpoint {
+    pub address: u32,
+    pub size: Size,
+    pub increment: bool,
+    /// SMOD or DMOD value, wrapping the address within an aligned block.
+    pub modulo: u8,
+}
+
+impl Endpoint {
+    pub fn memory<W: Word>(ptr: *const W) -> Self {
+        Self {
+            address: ptr as u32,
+            size: W::SIZE,
+            increment: true,
+            modulo: 0,
+        }
+    }
+
+    pub fn peripheral<W: Word>(ptr: *const W) -> Self {
+        Self {
+            address: ptr as u32,
+            size: W::SIZE,
+            increment: false,
+            modulo: 0,
+        }
+    }
+
+    /// The same endpoint, `bytes` further on if it increments.
+    pub fn offset(self, bytes: usize) -> Self {
+        if self.increment {
+            #[allow(clippy::cast_possible_truncation)]
+            let address = self.address + bytes as u32;
+            Self { address, ..self }
+        } else {
+            self
+        }
+    }
+}
+
+/// What moves a transfer along.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Trigger {
+    /// Run the whole transfer as soon as it is configured.
+    Start,
+    /// Move one word per DMAMUX request.
+    Request,
+    /// Move one word each time another channel links to this one.
+    Link,
+}
+
+/// LINKCC, LCH1 and LCH2 values.
+pub(crate) struct Linking {
+    pub mode: u8,
+    pub first: u8,
+    pub second: u8,
+}
+
+impl Linking {
+    pub const NONE: Self = Self {
+        mode: 0b00,
+        first: 0,
+        second: 0,
+    };
+}
+
+pub(crate) struct Config {
+    pub source: Endpoint,
+    pub dest: Endpoint,
+    pub bytes: usize,
+    pub trigger: Trigger,
+    /// Keep accepting requests once the count reaches zero, so the interrupt
+    /// can restart the transfer.
+    pub circular: bool,
+    pub link: Linking,
+}
+
+/// Route `request` to channel `D` in the DMAMUX, or disconnect it if `None`.
+pub(crate) fn set_request<D: Dma>(request: Option<Request>) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    peripherals.sim.scgc6().modify(|_, w| w.dmamux()._1());
+
+    // Reset DMAMUX
+    peripherals.dmamux0.chcfg(D::INDEX as usize).reset();
+
+    // Set slot number in DMAMUX, then enable.
+    if let Some(request) = request {
+        peripherals
+            .dmamux0
+            .chcfg(D::INDEX as usize)
+            .write(|w| unsafe { w.source().bits(request as u8) });
+        peripherals
+            .dmamux0
+            .chcfg(D::INDEX as usize)
+            .modify(|_, w| w.enbl()._1());
+    }
+}
+
+macro_rules! write_dcr {
+    ($dcr:expr, $config:expr) => {
+        $dcr.write(|w| {
+            w.sinc()
+                .bit($config.source.increment)
+                .ssize()
+                .bits($config.source.size.bits())
+                .smod()
+                .bits($config.source.modulo)
+                .dinc()
+                .bit($config.dest.increment)
+                .dsize()
+                .bits($config.dest.size.bits())
+                .dmod()
+                .bits($config.dest.modulo)
+                .erq()
+                .bit($config.trigger == Trigger::Request)
+                .cs()
+                .bit($config.trigger != Trigger::Start)
+                .d_req()
+                .bit($config.trigger == Trigger::Request && !$config.circular)
+                .linkcc()
+                .bits($config.link.mode)
+                .lch1()
+                .bits($config.link.first)
+                .lch2()
+                .bits($config.link.second)
+                .eint()
+                ._1()
+        })
+    };
+}
+
+/// # Safety
+/// Both endpoints must stay valid until the transfer completes, and channel
+/// `D` must not be in use.
+pub(crate) unsafe fn setup_dma_transfer<D: Dma>(config: &Config) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    #[allow(clippy::cast_possible_truncation)]
+    let bytes = config.bytes as u32; // Never more than `MAX_BYTES`.
+
+    STATUS[D::INDEX as usize].store(0, Ordering::Release);
+
+    // Reset DMA
+    match D::INDEX {
+        0 => peripherals.dma.dsr_bcr0().write(|w| w.done()._1()),
+        1 => peripherals.dma.dsr_bcr1().write(|w| w.done()._1()),
+        2 => peripherals.dma.dsr_bcr2().write(|w| w.done()._1()),
+        3 => peripherals.dma.dsr_bcr3().write(|w| w.done()._1()),
+
+        _ => unreachable!(),
+    };
+
+    // Set source address
+    match D::INDEX {
+        0 => peripherals
+            .dma
+            .sar0()
+            .write(|w| w.sar().bits(config.source.address)),
+        1 => peripherals
+            .dma
+            .sar1()
+            .write(|w| w.sar().bits(config.source.address)),
+        2 => peripherals
+            .dma
+            .sar2()
+            .write(|w| w.sar().bits(config.source.address)),
+        3 => peripherals
+            .dma
+            .sar3()
+            .write(|w| w.sar().bits(config.source.address)),
+
+        _ => unreachable!(),
+    };
+
+    // Set destination address
+    match D::INDEX {
+        0 => peripherals
+            .dma
+            .dar0()
+            .write(|w| w.dar().bits(config.dest.address)),
+        1 => peripherals
+            .dma
+            .dar1()
+            .write(|w| w.dar().bits(config.dest.address)),
+        2 => peripherals
+            .dma
+            .dar2()
+            .write(|w| w.dar().bits(config.dest.address)),
+        3 => peripherals
+            .dma
+            .dar3()
+            .write(|w| w.dar().bits(config.dest.address)),
+
+        _ => unreachable!(),
+    };
+
+    match D::INDEX {
+        0 => peripherals.dma.dsr_bcr0().write(|w| w.bcr().bits(bytes)),
+        1 => peripherals.dma.dsr_bcr1().write(|w| w.bcr().bits(bytes)),
+        2 => peripherals.dma.dsr_bcr2().write(|w| w.bcr().bits(bytes)),
+        3 => peripherals.dma.dsr_bcr3().write(|w| w.bcr().bits(bytes)),
+
+        _ => unreachable!(),
+    }
+
+    match D::INDEX {
+        0 => write_dcr!(peripherals.dma.dcr0(), config),
+        1 => write_dcr!(peripherals.dma.dcr1(), config),
+        2 => write_dcr!(peripherals.dma.dcr2(), config),
+        3 => write_dcr!(peripherals.dma.dcr3(), config),
+
+        _ => unreachable!(),
+    };
+
+    // Other transfers are started by a peripheral or linked channel.
+    if config.trigger == Trigger::Start {
+        match D::INDEX {
+            0 => peripherals.dma.dcr0().modify(|_, w| w.start()._1()),
+            1 => peripherals.dma.dcr1().modify(|_, w| w.start()._1()),
+            2 => peripherals.dma.dcr2().modify(|_, w| w.start()._1()),
+            3 => peripherals.dma.dcr3().modify(|_, w| w.start()._1()),
+
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub(crate) async unsafe fn wait_dma_transfer<D: Dma>() -> Result<(), Error> {
+    unsafe { cortex_m::peripheral::NVIC::unmask(interrupt_of::<D>()) };
+
+    core::future::poll_fn(|cx| {
+        WAKERS[D::INDEX as usize].register(cx.waker());
+
+        // Only the interrupt sets this, and only once per transfer.
+        match STATUS[D::INDEX as usize].load(Ordering::Acquire) {
+            0 => core::task::Poll::Pending,
+            status => {
+                STATUS[D::INDEX as usize].store(0, Ordering::Release);
+                core::task::Poll::Ready(Error::from_status(status))
+            }
+        }
+    })
+    .await
+}
+
+/// Stop channel `D`, whether or not its transfer has completed. No more
+/// memory is accessed once this returns.
+pub(crate) fn abort_dma_transfer<D: Dma>() {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    // Stop accepting requests, then writing DONE aborts whatever is left.
+    match D::INDEX {
+        0 => peripherals.dma.dcr0().modify(|_, w| w.erq()._0()),
+        1 => peripherals.dma.dcr1().modify(|_, w| w.erq()._0()),
+        2 => peripherals.dma.dcr2().modify(|_, w| w.erq()._0()),
+        3 => peripherals.dma.dcr3().modify(|_, w| w.erq()._0()),
+
+        _ => unreachable!(),
+    }
+
+    match D::INDEX {
+        0 => peripherals.dma.dsr_bcr0().write(|w| w.done()._1()),
+        1 => peripherals.dma.dsr_bcr1().write(|w| w.done()._1()),
+        2 => peripherals.dma.dsr_bcr2().write(|w| w.done()._1()),
+        3 => peripherals.dma.dsr_bcr3().write(|w| w.done()._1()),
+
+        _ => unreachable!(),
+    }
+
+    // Let the current read and write pair finish.
+    while match D::INDEX {
+        0 => peripherals.dma.dsr_bcr0().read().bsy().bit_is_set(),
+        1 => peripherals.dma.dsr_bcr1().read().bsy().bit_is_set(),
+        2 => peripherals.dma.dsr_bcr2().read().bsy().bit_is_set(),
+        3 => peripherals.dma.dsr_bcr3().read().bsy().bit_is_set(),
+
+        _ => unreachable!(),
+    } {}
+
+    // A channel linking to this one could otherwise restart it.
+    match D::INDEX {
+        0 => peripherals
+            .dma
+            .dsr_bcr0()
+            .write(|w| unsafe { w.bcr().bits(0) }),
+        1 => peripherals
+            .dma
+            .dsr_bcr1()
+            .write(|w| unsafe { w.bcr().bits(0) }),
+        2 => peripherals
+            .dma
+            .dsr_bcr2()
+            .write(|w| unsafe { w.bcr().bits(0) }),
+        3 => peripherals
+            .dma
+            .dsr_bcr3()
+            .write(|w| unsafe { w.bcr().bits(0) }),
+
+        _ => unreachable!(),
+    }
+
+    RELOAD[D::INDEX as usize].store(0, Ordering::Release);
+    STATUS[D::INDEX as usize].store(0, Ordering::Release);
+}
+
+/// Bytes channel `D` has left to move.
+pub(crate) fn remaining_bytes<D: Dma>() -> u32 {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match D::INDEX {
+        0 => peripherals.dma.dsr_bcr0().read().bcr().bits(),
+        1 => peripherals.dma.dsr_bcr1().read().bcr().bits(),
+        2 => peripherals.dma.dsr_bcr2().read().bcr().bits(),
+        3 => peripherals.dma.dsr_bcr3().read().bcr().bits(),
+
+        _ => unreachable!(),
+    }
+}
+
+fn interrupt_of<D: Dma>() -> pac::Interrupt {
+    match D::INDEX {
+        0 => pac::Interrupt::DMA0,
+        1 => pac::Interrupt::DMA1,
+        2 => pac::Interrupt::DMA2,
+        3 => pac::Interrupt::DMA3,
+
+        _ => unreachable!(),
+    }
+}
+
+fn on_interrupt(index: usize) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    let status = match index {
+        0 => peripherals.dma.dsr_bcr0().read().bits(),
+        1 => peripherals.dma.dsr_bcr1().read().bits(),
+        2 => peripherals.dma.dsr_bcr2().read().bits(),
+        3 => peripherals.dma.dsr_bcr3().read().bits(),
+
+        _ => unreachable!(),
+    };
+
+    // Clear DONE and any errors, which also deasserts the interrupt.
+    match index {
+        0 => peripherals.dma.dsr_bcr0().write(|w| w.done()._1()),
+        1 => peripherals.dma.dsr_bcr1().write(|w| w.done()._1()),
+        2 => peripherals.dma.dsr_bcr2().write(|w| w.done()._1()),
+        3 => peripherals.dma.dsr_bcr3().write(|w| w.done()._1()),
+
+        _ => unreachable!(),
+    }
+
+    // DONE is always set here, so this is never zero.
+    #[allow(clippy::cast_possible_truncation)]
+    let status = (status >> 24) as u8;
+
+    let reload = RELOAD[index].load(Ordering::Acquire);
+    if reload != 0 && Error::from_status(status).is_ok() {
+        // Carry on from where the address wrapped to.
+        match index {
+            0 => peripherals
+                .dma
+                .dsr_bcr0()
+                .write(|w| unsafe { w.bcr().bits(reload) }),
+            1 => peripherals
+                .dma
+                .dsr_bcr1()
+                .write(|w| unsafe { w.bcr().bits(reload) }),
+            2 => peripherals
+                .dma
+                .dsr_bcr2()
+                .write(|w| unsafe { w.bcr().bits(reload) }),
+            3 => peripherals
+                .dma
+                .dsr_bcr3()
+                .write(|w| unsafe { w.bcr().bits(reload) }),
+
+            _ => unreachable!(),
+        }
+
+        LAPS[index].store(
+            LAPS[index].load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Release,
+        );
+    } else {
+        STATUS[index].store(status, Ordering::Release);
+    }
+
+    WAKERS[index].wake();
+}
+
+#[interrupt]
+fn DMA0() {
+    on_interrupt(0);
+}
+
+#[interrupt]
+fn DMA1() {
+    on_interrupt(1);
+}
+
+#[interrupt]
+fn DMA2() {
+    on_interrupt(2);
+}
+
+#[interrupt]
+fn DMA3() {
+    on_interrupt(3);
+}
@@ -90,7 +90,7 @@ where
     }
 }
 
-fn set_data_direction<P: Pin>(out: bool) {
+pub(crate) fn set_data_direction<P: Pin>(out: bool) {
     let peripherals = unsafe { pac::Peripherals::steal() };
 
     let bit = u32::from(out);
@@ -121,7 +121,7 @@ fn set_data_direction<P: Pin>(out: bool) {
     };
 }
 
-fn set_data_output<P: Pin>(high: bool) {
+pub(crate) fn set_data_output<P: Pin>(high: bool) {
     let peripherals = unsafe { pac::Peripherals::steal() };
 
     if high {
@@ -177,7 +177,7 @@ fn set_data_output<P: Pin>(high: bool) {
     }
 }
 
-fn data_input<P: Pin>() -> bool {
+pub(crate) fn data_input<P: Pin>() -> bool {
     let peripherals = unsafe { pac::Peripherals::steal() };
     let word: u32 = match P::PORT {
         0 => peripherals.gpioa.pdir().read().pdi().bits(),
@@ -1,17 +1,26 @@
 #![no_std]
 #![allow(missing_docs)]
 
+pub mod clock;
+pub mod delay;
 pub mod dma;
 pub mod gpio;
 mod mux;
 pub mod pins;
 pub mod spi;
 pub mod tpm;
+pub mod watchdog;
+
+use core::sync::atomic::{AtomicBool, Ordering};
 
 pub use cortex_m_rt::entry;
 
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
 pub struct Peripherals {
     pub pins: pins::Pins,
+    pub mcg: clock::Mcg,
+    pub cop: watchdog::Cop,
     pub tpm0: tpm::Tpm0,
     pub tpm1: tpm::Tpm1,
     pub tpm2: tpm::Tpm2,
@@ -24,44 +33,37 @@ pub struct Peripherals {
 }
 
 impl Peripherals {
-    /// Steal the set of peripherals that represent this board.
+    /// Take the set of peripherals that represent this board. Returns `None`
+    /// if they have already been taken.
+    ///
+    /// Clocks are left in their reset mode until
+    /// [`clock::ClockConfig::freeze`], and the watchdog left running until
+    /// [`watchdog::Cop::disable`].
+    #[must_use]
+    pub fn take() -> Option<Self> {
+        critical_section::with(|_| {
+            if TAKEN.load(Ordering::Relaxed) {
+                None
+            } else {
+                Some(unsafe { Self::steal() })
+            }
+        })
+    }
+
+    /// Steal the set of peripherals that represent this board, like
+    /// [`Self::take`] without the check.
     ///
     /// # Safety
     /// Must only be called once, multiple sets of peripherals may cause
     /// undefined and unexpected behaviour.
     #[must_use]
     pub unsafe fn steal() -> Self {
-        let peripherals = pac::Peripherals::steal();
-
-        peripherals.sim.copc().write(|w| w.copt()._00()); // Disable watchdog
-
-        // Setup clock for 48Mhz operation.
-        {
-            peripherals.mcg.c2().write(|w| w.bits(0x14_u8));
-            peripherals.mcg.c1().write(|w| w.bits(0x90_u8));
-
-            while peripherals.mcg.s().read().oscinit0().bit_is_clear() {}
-            while peripherals.mcg.s().read().irefst().bit_is_set() {}
-            while !peripherals.mcg.s().read().clkst().is_10() {}
-
-            peripherals.mcg.c5().write(|w| w.prdiv0()._3());
-            peripherals.mcg.c6().write(|w| w.bits(0x40_u8));
-
-            while peripherals.mcg.s().read().pllst().bit_is_clear() {}
-            while peripherals.mcg.s().read().lock0().bit_is_clear() {}
-
-            peripherals.mcg.c1().write(|w| w.bits(0x10_u8));
-
-            while !peripherals.mcg.s().read().clkst().is_11() {}
-        }
-
-        peripherals
-            .sim
-            .sopt2()
-            .write(|w| w.tpmsrc()._01().pllfllsel()._1()); // Select PLL for TPM clock
+        TAKEN.store(true, Ordering::Relaxed);
 
         Self {
             pins: pins::Pins::new(),
+            mcg: clock::Mcg::new(),
+            cop: watchdog::Cop::new(),
             tpm0: tpm::Tpm0::new(),
             tpm1: tpm::Tpm1::new(),
             tpm2: tpm::Tpm2::new(),
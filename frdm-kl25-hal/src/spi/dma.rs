@@ -0,0 +1,297 @@
+//! Transfers using a pair of DMA channels, leaving the core free for the
+//! whole transfer.
+//!
+//! The buffers are only borrowed, as
+//! [`SpiBus`](embedded_hal_async::spi::SpiBus) requires, and a transfer is
+//! stopped when its future is dropped. A transfer future must not be leaked,
+//! for example with [`core::mem::forget`] on a boxed future, as the controller
+//! would keep accessing the buffers after their borrow ends. Where that can't
+//! be ruled out, use the owned-buffer transfers of [`Dma`](crate::dma::Dma).
+
+use core::marker::PhantomData;
+
+use embedded_hal_async::delay::DelayNs;
+use woven::{Either, Join, Race};
+
+use super::{Clocks, Error, MisoPin, MosiPin, SckPin, Spi, SpiConfig, SpiMaster, DUMMY};
+use crate::delay::Delay;
+use crate::dma::{Dma, Endpoint};
+use crate::pins::Pin;
+
+/// Backend moving data with a transmit and a receive DMA channel.
+pub struct DmaChannels<T, R> {
+    tx: T,
+    rx: R,
+    /// Delay and longest time allowed for each DMA transfer. Units of
+    /// microseconds.
+    timeout: Option<(Delay, u32)>,
+}
+
+impl<S, T, R, SCK, MOSI, MISO> SpiMaster<S, DmaChannels<T, R>, SCK, MOSI, MISO>
+where
+    S: Spi,
+    T: Dma,
+    R: Dma,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        spi: S,
+        tx_ch: T,
+        rx_ch: R,
+        sck: SCK,
+        mosi: MOSI,
+        miso: MISO,
+        config: SpiConfig,
+        clocks: &Clocks,
+    ) -> Self {
+        crate::dma::set_request::<T>(Some(S::TX_REQUEST));
+        crate::dma::set_request::<R>(Some(S::RX_REQUEST));
+
+        Self::new_inner(
+            spi,
+            DmaChannels {
+                tx: tx_ch,
+                rx: rx_ch,
+                timeout: None,
+            },
+            sck,
+            mosi,
+            miso,
+            &config,
+            clocks,
+        )
+    }
+
+    /// Abort any DMA transfer taking longer than `timeout_us`, returning
+    /// [`Error::Timeout`]. Transfers longer than
+    /// [`MAX_BYTES`](crate::dma::MAX_BYTES) are split, each part getting the
+    /// full timeout.
+    #[must_use]
+    pub fn with_timeout(mut self, delay: Delay, timeout_us: u32) -> Self {
+        self.backend.timeout = Some((delay, timeout_us));
+        self
+    }
+
+    pub fn release(self) -> (S, T, R, SCK, MOSI, MISO) {
+        let (spi, backend, sck, mosi, miso) = self.into_parts();
+        (spi, backend.tx, backend.rx, sck, mosi, miso)
+    }
+}
+
+impl<S, T, R, SCK, MOSI, MISO> SpiMaster<S, DmaChannels<T, R>, SCK, MOSI, MISO>
+where
+    S: Spi,
+    T: Dma,
+    R: Dma,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    /// Exchange `len` bytes, split into as many DMA transfers as needed.
+    async fn transfer_inner(
+        &self,
+        read: Endpoint,
+        write: Endpoint,
+        len: usize,
+    ) -> Result<(), Error> {
+        let mut offset = 0;
+        while offset < len {
+            let bytes = (len - offset).min(crate::dma::MAX_BYTES);
+            self.transfer_chunk(read.offset(offset), write.offset(offset), bytes)
+                .await?;
+            offset += bytes;
+        }
+
+        Ok(())
+    }
+
+    async fn transfer_chunk(
+        &self,
+        read: Endpoint,
+        write: Endpoint,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        super::discard_stale::<S>();
+
+        let data_address = match S::INDEX {
+            0 => peripherals.spi0.d().as_ptr(),
+            1 => peripherals.spi1.d().as_ptr(),
+
+            _ => unreachable!(),
+        };
+
+        unsafe {
+            crate::dma::setup_dma_transfer::<T>(&crate::dma::Config {
+                source: write,
+                dest: Endpoint::peripheral(data_address),
+                bytes,
+                trigger: crate::dma::Trigger::Request,
+                circular: false,
+                link: crate::dma::Linking::NONE,
+            });
+
+            crate::dma::setup_dma_transfer::<R>(&crate::dma::Config {
+                source: Endpoint::peripheral(data_address),
+                dest: read,
+                bytes,
+                trigger: crate::dma::Trigger::Request,
+                circular: false,
+                link: crate::dma::Linking::NONE,
+            });
+        }
+
+        let _guard = TransferGuard::<S, T, R> {
+            _marker: PhantomData,
+        };
+
+        // Enable DMA in SPI block
+        match S::INDEX {
+            0 => peripherals
+                .spi0
+                .c2()
+                .modify(|_, w| w.rxdmae()._1().txdmae()._1()),
+            1 => peripherals
+                .spi1
+                .c2()
+                .modify(|_, w| w.rxdmae()._1().txdmae()._1()),
+
+            _ => unreachable!(),
+        }
+
+        let transfer = async {
+            let (tx, rx) = unsafe {
+                (
+                    crate::dma::wait_dma_transfer::<T>(),
+                    crate::dma::wait_dma_transfer::<R>(),
+                )
+                    .join()
+                    .await
+            };
+
+            tx.map_err(Error::Transmit)?;
+            rx.map_err(Error::Receive)
+        };
+
+        let Some((mut delay, timeout_us)) = self.backend.timeout else {
+            return transfer.await;
+        };
+
+        match (transfer, delay.delay_us(timeout_us)).race().await {
+            Either::First(result) => result,
+            // Both channels are aborted by the guard.
+            Either::Second(()) => Err(Error::Timeout),
+        }
+    }
+}
+
+/// Stops a DMA transfer when dropped, including when the transfer future is
+/// cancelled, so the buffers are no longer accessed once their borrow ends.
+pub(super) struct TransferGuard<S: Spi, T: Dma, R: Dma> {
+    pub(super) _marker: PhantomData<(S, T, R)>,
+}
+
+impl<S: Spi, T: Dma, R: Dma> Drop for TransferGuard<S, T, R> {
+    fn drop(&mut self) {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        // Disable DMA in SPI block
+        match S::INDEX {
+            0 => peripherals
+                .spi0
+                .c2()
+                .modify(|_, w| w.rxdmae()._0().txdmae()._0()),
+            1 => peripherals
+                .spi1
+                .c2()
+                .modify(|_, w| w.rxdmae()._0().txdmae()._0()),
+
+            _ => unreachable!(),
+        }
+
+        crate::dma::abort_dma_transfer::<T>();
+        crate::dma::abort_dma_transfer::<R>();
+    }
+}
+
+impl<S, T, R, SCK, MOSI, MISO> embedded_hal_async::spi::ErrorType
+    for SpiMaster<S, DmaChannels<T, R>, SCK, MOSI, MISO>
+{
+    type Error = Error;
+}
+
+impl<S, T, R, SCK, MOSI, MISO> embedded_hal_async::spi::SpiBus
+    for SpiMaster<S, DmaChannels<T, R>, SCK, MOSI, MISO>
+where
+    S: Spi,
+    T: Dma,
+    R: Dma,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transfer_inner(
+            Endpoint::memory(words.as_mut_ptr()),
+            Endpoint::peripheral(&raw const DUMMY),
+            words.len(),
+        )
+        .await
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        let mut discard = 0_u8;
+        self.transfer_inner(
+            Endpoint::peripheral(&raw mut discard),
+            Endpoint::memory(words.as_ptr()),
+            words.len(),
+        )
+        .await
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        let common = read.len().min(write.len());
+
+        self.transfer_inner(
+            Endpoint::memory(read.as_mut_ptr()),
+            Endpoint::memory(write.as_ptr()),
+            common,
+        )
+        .await?;
+
+        // Only one of these has anything left.
+        self.read(&mut read[common..]).await?;
+        self.write(&write[common..]).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        // Each byte is sent before the byte received in its place is written.
+        let len = words.len();
+        let words = words.as_mut_ptr();
+        self.transfer_inner(
+            Endpoint::memory(words),
+            Endpoint::memory(words.cast_const()),
+            len,
+        )
+        .await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        // Every transfer waits until its last byte is received, so only the
+        // transmit buffer can still be full.
+        match S::INDEX {
+            0 => while peripherals.spi0.s().read().sptef().bit_is_clear() {},
+            1 => while peripherals.spi1.s().read().sptef().bit_is_clear() {},
+
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+}
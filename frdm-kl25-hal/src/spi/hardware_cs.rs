@@ -0,0 +1,239 @@
+//! A single device whose chip select is driven by the SPI block itself on its
+//! SS pin, using MODFEN and SSOE.
+//!
+//! The block only holds SS low while its transmit buffer is kept full, so
+//! only a transaction sent as one frame without gaps can use it: a single
+//! read, write, in place transfer, or transfer with equal buffer lengths. For
+//! any other transaction SS is switched to a GPIO output and held low by
+//! software until every operation has finished, then handed back to the
+//! block.
+//!
+//! With the [`DmaChannels`] backend a hardware frame must also fit in one DMA
+//! transfer, at most [`MAX_BYTES`](crate::dma::MAX_BYTES). The transmit
+//! channel refills the buffer within a few bus cycles of SPTEF setting, while
+//! each byte takes at least 16 cycles of the block's input clock to shift
+//! out. So SS stays low from the first byte of a frame to the last, for
+//! example across the 16-bit frames of the MC33HB2001.
+//!
+//! With the [`Polling`] backend the device is blocking, for use from
+//! interrupt handlers. The core writes each byte while the one before it
+//! shifts out, inside a critical section so nothing can delay the refill.
+
+use core::convert::Infallible;
+
+use embedded_hal_async::spi::Operation;
+
+use super::{DmaChannels, Error, MisoPin, MosiPin, Polling, SckPin, Spi, SpiMaster, SsPin};
+use crate::delay::Delay;
+use crate::dma::Dma;
+use crate::mux::Alternate;
+use crate::pins::Pin;
+
+/// Exclusive device on a bus with hardware chip select.
+pub struct HardwareCsDevice<S, B, SCK, MOSI, MISO, SS> {
+    bus: SpiMaster<S, B, SCK, MOSI, MISO>,
+    ss: SS,
+    delay: Delay,
+}
+
+impl<S, B, SCK, MOSI, MISO, SS> HardwareCsDevice<S, B, SCK, MOSI, MISO, SS>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+    SS: SsPin<S> + Pin,
+{
+    /// `delay` times the [`Operation::DelayNs`] steps of a transaction.
+    pub fn new(bus: SpiMaster<S, B, SCK, MOSI, MISO>, ss: SS, delay: Delay) -> Self {
+        crate::pins::enable_port_clock::<SS>();
+        crate::mux::set_alternate::<SS>(SS::ALT);
+
+        set_automatic_ss::<S>(true);
+
+        Self { bus, ss, delay }
+    }
+
+    pub fn release(self) -> (SpiMaster<S, B, SCK, MOSI, MISO>, SS) {
+        set_automatic_ss::<S>(false);
+
+        (self.bus, self.ss)
+    }
+}
+
+/// Switch SS between a mode fault input and the automatic chip select output.
+fn set_automatic_ss<S: Spi>(enabled: bool) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => {
+            peripherals.spi0.c2().modify(|_, w| w.modfen().bit(enabled));
+            peripherals.spi0.c1().modify(|_, w| w.ssoe().bit(enabled));
+        }
+        1 => {
+            peripherals.spi1.c2().modify(|_, w| w.modfen().bit(enabled));
+            peripherals.spi1.c1().modify(|_, w| w.ssoe().bit(enabled));
+        }
+
+        _ => unreachable!(),
+    }
+}
+
+/// Take SS from the block and drive it low as a GPIO output. It is driven
+/// high before the switch, so it doesn't glitch.
+fn select_by_gpio<SS: Pin>() {
+    crate::gpio::set_data_output::<SS>(true);
+    crate::gpio::set_data_direction::<SS>(true);
+    crate::mux::set_alternate::<SS>(Alternate::Gpio);
+    crate::gpio::set_data_output::<SS>(false);
+}
+
+/// Drive SS high and hand it back to the block, which also keeps it high
+/// while idle.
+fn deselect_by_gpio<S, SS: SsPin<S> + Pin>() {
+    crate::gpio::set_data_output::<SS>(true);
+    crate::mux::set_alternate::<SS>(SS::ALT);
+}
+
+/// The only operation of `operations`, and the number of bytes in its frame,
+/// if the transaction can be sent as one frame of at most `max_len` bytes.
+fn single_frame<'a, 'b>(
+    operations: &'a mut [Operation<'b, u8>],
+    max_len: usize,
+) -> Option<(&'a mut Operation<'b, u8>, usize)> {
+    let [operation] = operations else {
+        return None;
+    };
+
+    let len = match operation {
+        Operation::Read(words) | Operation::TransferInPlace(words) => words.len(),
+        Operation::Write(words) => words.len(),
+        Operation::Transfer(read, write) if read.len() == write.len() => read.len(),
+        Operation::Transfer(..) | Operation::DelayNs(_) => return None,
+    };
+
+    (len <= max_len).then_some((operation, len))
+}
+
+impl<S, B, SCK, MOSI, MISO, SS> embedded_hal_async::spi::ErrorType
+    for HardwareCsDevice<S, B, SCK, MOSI, MISO, SS>
+{
+    type Error = Error;
+}
+
+impl<S, T, R, SCK, MOSI, MISO, SS> embedded_hal_async::spi::SpiDevice
+    for HardwareCsDevice<S, DmaChannels<T, R>, SCK, MOSI, MISO, SS>
+where
+    S: Spi,
+    T: Dma,
+    R: Dma,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+    SS: SsPin<S> + Pin,
+{
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        use embedded_hal_async::delay::DelayNs;
+        use embedded_hal_async::spi::SpiBus;
+
+        if let Some((operation, _)) = single_frame(operations, crate::dma::MAX_BYTES) {
+            match operation {
+                Operation::Read(words) => self.bus.read(words).await?,
+                Operation::Write(words) => self.bus.write(words).await?,
+                Operation::Transfer(read, write) => self.bus.transfer(read, write).await?,
+                Operation::TransferInPlace(words) => self.bus.transfer_in_place(words).await?,
+                Operation::DelayNs(_) => unreachable!(),
+            }
+
+            return self.bus.flush().await;
+        }
+
+        select_by_gpio::<SS>();
+
+        let result = async {
+            for operation in operations {
+                match operation {
+                    Operation::Read(words) => self.bus.read(words).await?,
+                    Operation::Write(words) => self.bus.write(words).await?,
+                    Operation::Transfer(read, write) => self.bus.transfer(read, write).await?,
+                    Operation::TransferInPlace(words) => {
+                        self.bus.transfer_in_place(words).await?;
+                    }
+                    Operation::DelayNs(ns) => {
+                        self.bus.flush().await?;
+                        self.delay.delay_ns(*ns).await;
+                    }
+                }
+            }
+
+            self.bus.flush().await
+        }
+        .await;
+
+        // A failed or timed out transfer is aborted, so the device can be
+        // deselected straight away.
+        deselect_by_gpio::<S, SS>();
+
+        result
+    }
+}
+
+impl<S, SCK, MOSI, MISO, SS> embedded_hal::spi::SpiDevice
+    for HardwareCsDevice<S, Polling, SCK, MOSI, MISO, SS>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+    SS: SsPin<S> + Pin,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        use embedded_hal::delay::DelayNs;
+        use embedded_hal::spi::SpiBus;
+
+        if let Some((operation, len)) = single_frame(operations, usize::MAX) {
+            let (read, write) = match operation {
+                Operation::Read(words) => (words.as_mut_ptr(), core::ptr::null()),
+                Operation::Write(words) => (core::ptr::null_mut(), words.as_ptr()),
+                Operation::Transfer(read, write) => (read.as_mut_ptr(), write.as_ptr()),
+                Operation::TransferInPlace(words) => {
+                    let words = words.as_mut_ptr();
+                    (words, words.cast_const())
+                }
+                Operation::DelayNs(_) => unreachable!(),
+            };
+
+            // Every byte has been received when this returns, so there is
+            // nothing left to flush.
+            critical_section::with(|_| unsafe {
+                SpiMaster::<S, Polling, SCK, MOSI, MISO>::exchange_frame(read, write, len);
+            });
+
+            return Ok(());
+        }
+
+        select_by_gpio::<SS>();
+
+        for operation in operations {
+            let Ok(()): Result<(), Infallible> = match operation {
+                Operation::Read(words) => self.bus.read(words),
+                Operation::Write(words) => self.bus.write(words),
+                Operation::Transfer(read, write) => self.bus.transfer(read, write),
+                Operation::TransferInPlace(words) => self.bus.transfer_in_place(words),
+                Operation::DelayNs(ns) => {
+                    let Ok(()) = self.bus.flush();
+                    self.delay.delay_ns(*ns);
+                    Ok(())
+                }
+            };
+        }
+
+        let Ok(()) = self.bus.flush();
+        deselect_by_gpio::<S, SS>();
+
+        Ok(())
+    }
+}
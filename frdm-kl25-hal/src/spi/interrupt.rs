@@ -0,0 +1,190 @@
+//! Async transfers woken by the SPI receive interrupt, one byte at a time,
+//! without using any DMA channels.
+
+use core::convert::Infallible;
+
+use cortex_m_rt::interrupt;
+use embassy_sync::waitqueue::AtomicWaker;
+use pac::Interrupt as interrupt;
+
+use super::{Clocks, MisoPin, MosiPin, SckPin, Spi, SpiConfig, SpiMaster, DUMMY};
+use crate::pins::Pin;
+
+/// Also woken by [`SpiSlave`](super::SpiSlave) command matches.
+pub(super) static WAKERS: [AtomicWaker; 2] = [const { AtomicWaker::new() }; 2];
+
+/// Backend moving data from the SPRF interrupt.
+pub struct Interrupt {
+    _private: (),
+}
+
+impl<S, SCK, MOSI, MISO> SpiMaster<S, Interrupt, SCK, MOSI, MISO>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    pub fn new_interrupt(
+        spi: S,
+        sck: SCK,
+        mosi: MOSI,
+        miso: MISO,
+        config: SpiConfig,
+        clocks: &Clocks,
+    ) -> Self {
+        let this = Self::new_inner(
+            spi,
+            Interrupt { _private: () },
+            sck,
+            mosi,
+            miso,
+            &config,
+            clocks,
+        );
+
+        unsafe { cortex_m::peripheral::NVIC::unmask(interrupt_of::<S>()) };
+
+        this
+    }
+
+    pub fn release(self) -> (S, SCK, MOSI, MISO) {
+        cortex_m::peripheral::NVIC::mask(interrupt_of::<S>());
+        set_receive_interrupt::<S>(false);
+
+        let (spi, _, sck, mosi, miso) = self.into_parts();
+        (spi, sck, mosi, miso)
+    }
+
+    async fn exchange(byte: u8) -> u8 {
+        // The previous byte has always been received, so this never spins.
+        while !super::transmit_empty::<S>() {}
+        super::write_data::<S>(byte);
+
+        core::future::poll_fn(|cx| {
+            WAKERS[S::INDEX as usize].register(cx.waker());
+
+            if super::receive_full::<S>() {
+                core::task::Poll::Ready(super::read_data::<S>())
+            } else {
+                set_receive_interrupt::<S>(true);
+                core::task::Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<S, SCK, MOSI, MISO> embedded_hal_async::spi::ErrorType
+    for SpiMaster<S, Interrupt, SCK, MOSI, MISO>
+{
+    type Error = Infallible;
+}
+
+impl<S, SCK, MOSI, MISO> embedded_hal_async::spi::SpiBus
+    for SpiMaster<S, Interrupt, SCK, MOSI, MISO>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for word in words {
+            *word = Self::exchange(DUMMY).await;
+        }
+
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for word in words {
+            Self::exchange(*word).await;
+        }
+
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for i in 0..read.len().max(write.len()) {
+            let byte = Self::exchange(write.get(i).copied().unwrap_or(DUMMY)).await;
+            if let Some(word) = read.get_mut(i) {
+                *word = byte;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for word in words {
+            *word = Self::exchange(*word).await;
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Every byte is received before the next is sent.
+        Ok(())
+    }
+}
+
+fn set_receive_interrupt<S: Spi>(enabled: bool) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals.spi0.c1().modify(|_, w| w.spie().bit(enabled)),
+        1 => peripherals.spi1.c1().modify(|_, w| w.spie().bit(enabled)),
+
+        _ => unreachable!(),
+    }
+}
+
+pub(super) fn interrupt_of<S: Spi>() -> pac::Interrupt {
+    match S::INDEX {
+        0 => pac::Interrupt::SPI0,
+        1 => pac::Interrupt::SPI1,
+
+        _ => unreachable!(),
+    }
+}
+
+fn on_interrupt(index: usize) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    // SPRF and SPMF stay set until handled by the woken task, so mask them
+    // until it waits again.
+    match index {
+        0 => {
+            peripherals.spi0.c1().modify(|_, w| w.spie()._0());
+            peripherals.spi0.c2().modify(|_, w| w.spmie()._0());
+        }
+        1 => {
+            peripherals.spi1.c1().modify(|_, w| w.spie()._0());
+            peripherals.spi1.c2().modify(|_, w| w.spmie()._0());
+        }
+
+        _ => unreachable!(),
+    }
+
+    WAKERS[index].wake();
+}
+
+#[interrupt]
+fn SPI0() {
+    on_interrupt(0);
+}
+
+#[interrupt]
+fn SPI1() {
+    on_interrupt(1);
+}
@@ -0,0 +1,424 @@
+#![allow(clippy::module_name_repetitions)]
+
+pub mod hardware_cs;
+pub mod shared;
+
+mod dma;
+mod interrupt;
+mod polling;
+mod slave;
+
+use embedded_hal::spi::{Mode, Phase, Polarity};
+
+pub use self::dma::DmaChannels;
+pub use self::interrupt::Interrupt;
+pub use self::polling::Polling;
+pub use self::slave::SpiSlave;
+use crate::clock::Clocks;
+use crate::dma::Request;
+use crate::pins::Pin;
+
+pub trait Spi {
+    const INDEX: u32;
+    const RX_REQUEST: Request;
+    const TX_REQUEST: Request;
+}
+
+pub struct Spi0 {
+    _private: (),
+}
+
+impl Spi0 {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Spi for Spi0 {
+    const INDEX: u32 = 0;
+    const RX_REQUEST: Request = Request::Spi0Rx;
+    const TX_REQUEST: Request = Request::Spi0Tx;
+}
+
+pub struct Spi1 {
+    _private: (),
+}
+
+impl Spi1 {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Spi for Spi1 {
+    const INDEX: u32 = 1;
+    const RX_REQUEST: Request = Request::Spi1Rx;
+    const TX_REQUEST: Request = Request::Spi1Tx;
+}
+
+/// Possible SPI errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Error {
+    /// The transmit DMA channel stopped with an error.
+    Transmit(crate::dma::Error),
+    /// The receive DMA channel stopped with an error.
+    Receive(crate::dma::Error),
+    /// A DMA transfer took longer than the timeout set with
+    /// [`SpiMaster::with_timeout`], and was aborted.
+    Timeout,
+}
+
+impl embedded_hal_async::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_async::spi::ErrorKind {
+        embedded_hal_async::spi::ErrorKind::Other
+    }
+}
+
+/// Order bits are shifted out and in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+/// SPI bus configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiConfig {
+    /// Target clock frequency, rounded down to the nearest achievable rate.
+    /// Units of Hertz.
+    pub frequency_hz: u32,
+    pub mode: Mode,
+    pub bit_order: BitOrder,
+}
+
+impl Default for SpiConfig {
+    fn default() -> Self {
+        Self {
+            frequency_hz: 1_500_000,
+            mode: embedded_hal::spi::MODE_1,
+            bit_order: BitOrder::MsbFirst,
+        }
+    }
+}
+
+/// Buses whose configuration can be changed between transactions.
+pub trait SetConfig {
+    /// Apply `config`, returning the actual clock frequency. Units of Hertz.
+    fn set_config(&mut self, config: &SpiConfig) -> u32;
+}
+
+pub trait MosiPin<S> {
+    const ALT: crate::mux::Alternate;
+}
+
+pub trait MisoPin<S> {
+    const ALT: crate::mux::Alternate;
+}
+
+pub trait SckPin<S> {
+    const ALT: crate::mux::Alternate;
+}
+
+pub trait SsPin<S> {
+    const ALT: crate::mux::Alternate;
+}
+
+/// SPI master, moving data with the backend `B`. See [`DmaChannels`],
+/// [`Polling`] and [`Interrupt`].
+pub struct SpiMaster<S, B, SCK, MOSI, MISO> {
+    spi: S,
+    backend: B,
+    sck: SCK,
+    mosi: MOSI,
+    miso: MISO,
+    /// Input clock of the block. Units of Hertz.
+    clock_hz: u32,
+    frequency_hz: u32,
+}
+
+impl<S, B, SCK, MOSI, MISO> SpiMaster<S, B, SCK, MOSI, MISO>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    fn new_inner(
+        spi: S,
+        backend: B,
+        sck: SCK,
+        mosi: MOSI,
+        miso: MISO,
+        config: &SpiConfig,
+        clocks: &Clocks,
+    ) -> Self {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        crate::pins::enable_port_clock::<SCK>();
+        crate::pins::enable_port_clock::<MISO>();
+        crate::pins::enable_port_clock::<MOSI>();
+
+        crate::mux::set_alternate::<SCK>(SCK::ALT);
+        crate::mux::set_alternate::<MISO>(MISO::ALT);
+        crate::mux::set_alternate::<MOSI>(MOSI::ALT);
+
+        // Enable spi clock
+        match S::INDEX {
+            0 => peripherals.sim.scgc4().modify(|_, w| w.spi0()._1()),
+            1 => peripherals.sim.scgc4().modify(|_, w| w.spi1()._1()),
+
+            _ => unreachable!(),
+        };
+
+        // Start from reset, `configure` keeps any other bits already set.
+        match S::INDEX {
+            0 => {
+                peripherals.spi0.c1().reset();
+                peripherals.spi0.c2().reset();
+            }
+            1 => {
+                peripherals.spi1.c1().reset();
+                peripherals.spi1.c2().reset();
+            }
+
+            _ => unreachable!(),
+        }
+
+        let clock_hz = input_clock_hz::<S>(clocks);
+        let frequency_hz = configure::<S>(config, clock_hz);
+
+        Self {
+            spi,
+            backend,
+            sck,
+            mosi,
+            miso,
+            clock_hz,
+            frequency_hz,
+        }
+    }
+
+    fn into_parts(self) -> (S, B, SCK, MOSI, MISO) {
+        (self.spi, self.backend, self.sck, self.mosi, self.miso)
+    }
+}
+
+impl<S, B, SCK, MOSI, MISO> SpiMaster<S, B, SCK, MOSI, MISO> {
+    /// Actual clock frequency. Units of Hertz.
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz
+    }
+}
+
+impl<S, B, SCK, MOSI, MISO> SetConfig for SpiMaster<S, B, SCK, MOSI, MISO>
+where
+    S: Spi,
+{
+    fn set_config(&mut self, config: &SpiConfig) -> u32 {
+        self.frequency_hz = configure::<S>(config, self.clock_hz);
+        self.frequency_hz
+    }
+}
+
+/// Apply `config` to SPI block `S` in master mode, returning the actual clock
+/// frequency.
+fn configure<S: Spi>(config: &SpiConfig, clock_hz: u32) -> u32 {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    let (prescaler, divisor, frequency_hz) = baud_divisors(clock_hz, config.frequency_hz);
+
+    let idle_high = config.mode.polarity == Polarity::IdleHigh;
+    let second_edge = config.mode.phase == Phase::CaptureOnSecondTransition;
+    let lsb_first = config.bit_order == BitOrder::LsbFirst;
+
+    // Disable SPI while it is reconfigured
+    match S::INDEX {
+        0 => peripherals.spi0.c1().modify(|_, w| w.spe()._0()),
+        1 => peripherals.spi1.c1().modify(|_, w| w.spe()._0()),
+
+        _ => unreachable!(),
+    }
+
+    // Set baud rate, SPR is never more than 8.
+    match S::INDEX {
+        0 => peripherals
+            .spi0
+            .br()
+            .write(|w| unsafe { w.sppr().bits(prescaler).spr().bits(divisor) }),
+        1 => peripherals
+            .spi1
+            .br()
+            .write(|w| unsafe { w.sppr().bits(prescaler).spr().bits(divisor) }),
+
+        _ => unreachable!(),
+    }
+
+    // Enable SPI, master mode. Modified rather than written so SSOE and the
+    // interrupt enables set by a device or backend survive reconfiguration.
+    match S::INDEX {
+        0 => peripherals.spi0.c1().modify(|_, w| {
+            w.spe()
+                ._1()
+                .mstr()
+                ._1()
+                .cpol()
+                .bit(idle_high)
+                .cpha()
+                .bit(second_edge)
+                .lsbfe()
+                .bit(lsb_first)
+        }),
+        1 => peripherals.spi1.c1().modify(|_, w| {
+            w.spe()
+                ._1()
+                .mstr()
+                ._1()
+                .cpol()
+                .bit(idle_high)
+                .cpha()
+                .bit(second_edge)
+                .lsbfe()
+                .bit(lsb_first)
+        }),
+
+        _ => unreachable!(),
+    }
+
+    frequency_hz
+}
+
+/// SPI0 is clocked from the bus clock, SPI1 from the system clock.
+fn input_clock_hz<S: Spi>(clocks: &Clocks) -> u32 {
+    match S::INDEX {
+        0 => clocks.bus_hz(),
+        1 => clocks.core_hz(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Find the SPPR and SPR values giving the fastest rate not above
+/// `target_hz`, or the slowest rate if all are above it.
+fn baud_divisors(clock_hz: u32, target_hz: u32) -> (u8, u8, u32) {
+    let mut best = (0b111, 8, clock_hz / (8 << 9));
+
+    for divisor in 0..=8 {
+        for prescaler in 0..=0b111 {
+            let rate = clock_hz / ((u32::from(prescaler) + 1) << (divisor + 1));
+            if rate <= target_hz && rate > best.2 {
+                best = (prescaler, divisor, rate);
+            }
+        }
+    }
+
+    best
+}
+
+/// Sent while reading.
+static DUMMY: u8 = 0;
+
+/// Whether the transmit buffer of SPI block `S` can take another byte.
+fn transmit_empty<S: Spi>() -> bool {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals.spi0.s().read().sptef().bit_is_set(),
+        1 => peripherals.spi1.s().read().sptef().bit_is_set(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Whether SPI block `S` has received a byte.
+fn receive_full<S: Spi>() -> bool {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals.spi0.s().read().sprf().bit_is_set(),
+        1 => peripherals.spi1.s().read().sprf().bit_is_set(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Must only be called once [`transmit_empty`] returns true.
+fn write_data<S: Spi>(byte: u8) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals
+            .spi0
+            .d()
+            .write(|w| unsafe { w.bits_().bits(byte) }),
+        1 => peripherals
+            .spi1
+            .d()
+            .write(|w| unsafe { w.bits_().bits(byte) }),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Clears the receive flag if [`receive_full`] returned true.
+fn read_data<S: Spi>() -> u8 {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals.spi0.d().read().bits_().bits(),
+        1 => peripherals.spi1.d().read().bits_().bits(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Drop a byte left behind by a cancelled transfer.
+fn discard_stale<S: Spi>() {
+    if receive_full::<S>() {
+        let _ = read_data::<S>();
+    }
+}
+
+macro_rules! s_pin {
+    ($pin:ident, $tpm:ident, $p_type:ident, $alt:ident) => {
+        impl $p_type<$tpm> for crate::pins::$pin {
+            const ALT: crate::mux::Alternate = crate::mux::Alternate::$alt;
+        }
+    };
+}
+
+s_pin!(PTE1, Spi1, MosiPin, Alt2);
+s_pin!(PTE1, Spi1, MisoPin, Alt5);
+s_pin!(PTE2, Spi1, SckPin, Alt2);
+s_pin!(PTE3, Spi1, MisoPin, Alt2);
+s_pin!(PTE3, Spi1, MosiPin, Alt5);
+s_pin!(PTE4, Spi1, SsPin, Alt2);
+s_pin!(PTA14, Spi0, SsPin, Alt2);
+s_pin!(PTA15, Spi0, SckPin, Alt2);
+s_pin!(PTA16, Spi0, MosiPin, Alt2);
+s_pin!(PTA16, Spi0, MisoPin, Alt5);
+s_pin!(PTA17, Spi0, MisoPin, Alt2);
+s_pin!(PTA17, Spi0, MosiPin, Alt5);
+s_pin!(PTB10, Spi1, SsPin, Alt2);
+s_pin!(PTB11, Spi1, SckPin, Alt2);
+s_pin!(PTB16, Spi1, MosiPin, Alt2);
+s_pin!(PTB16, Spi1, MisoPin, Alt5);
+s_pin!(PTB17, Spi1, MisoPin, Alt2);
+s_pin!(PTB17, Spi1, MosiPin, Alt5);
+s_pin!(PTC4, Spi0, SsPin, Alt2);
+s_pin!(PTC5, Spi0, SckPin, Alt2);
+s_pin!(PTC6, Spi0, MosiPin, Alt2);
+s_pin!(PTC6, Spi0, MisoPin, Alt5);
+s_pin!(PTC7, Spi0, MisoPin, Alt2);
+s_pin!(PTC7, Spi0, MosiPin, Alt5);
+s_pin!(PTD0, Spi0, SsPin, Alt2);
+s_pin!(PTD1, Spi0, SckPin, Alt2);
+s_pin!(PTD2, Spi0, MosiPin, Alt2);
+s_pin!(PTD2, Spi0, MisoPin, Alt5);
+s_pin!(PTD3, Spi0, MisoPin, Alt2);
+s_pin!(PTD3, Spi0, MosiPin, Alt5);
+s_pin!(PTD4, Spi1, SsPin, Alt2);
+s_pin!(PTD5, Spi1, SckPin, Alt2);
+s_pin!(PTD6, Spi1, MosiPin, Alt2);
+s_pin!(PTD6, Spi1, MisoPin, Alt5);
+s_pin!(PTD7, Spi1, MisoPin, Alt2);
+s_pin!(PTD7, Spi1, MosiPin, Alt5);
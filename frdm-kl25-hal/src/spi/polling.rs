@@ -0,0 +1,153 @@
+//! Blocking transfers that spin on the status flags. Best for short frames,
+//! where setting up DMA would take longer than the frame itself.
+
+use core::convert::Infallible;
+
+use super::{Clocks, MisoPin, MosiPin, SckPin, Spi, SpiConfig, SpiMaster, DUMMY};
+use crate::pins::Pin;
+
+/// Backend moving data by polling SPTEF and SPRF.
+pub struct Polling {
+    _private: (),
+}
+
+impl<S, SCK, MOSI, MISO> SpiMaster<S, Polling, SCK, MOSI, MISO>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    pub fn new_polling(
+        spi: S,
+        sck: SCK,
+        mosi: MOSI,
+        miso: MISO,
+        config: SpiConfig,
+        clocks: &Clocks,
+    ) -> Self {
+        Self::new_inner(
+            spi,
+            Polling { _private: () },
+            sck,
+            mosi,
+            miso,
+            &config,
+            clocks,
+        )
+    }
+
+    pub fn release(self) -> (S, SCK, MOSI, MISO) {
+        let (spi, _, sck, mosi, miso) = self.into_parts();
+        (spi, sck, mosi, miso)
+    }
+
+    fn exchange(byte: u8) -> u8 {
+        while !super::transmit_empty::<S>() {}
+        super::write_data::<S>(byte);
+
+        while !super::receive_full::<S>() {}
+        super::read_data::<S>()
+    }
+
+    /// Exchange `len` bytes as one frame, writing each byte while the one
+    /// before it shifts out so the transmit buffer never runs empty. Either
+    /// pointer may be null, sending [`DUMMY`] or discarding what is received.
+    /// Must not be interrupted for longer than one byte takes to shift out.
+    ///
+    /// # Safety
+    /// `read` and `write` must each be null or valid for `len` bytes. They
+    /// may point to the same buffer, each byte is sent before the byte
+    /// received in its place is written.
+    pub(super) unsafe fn exchange_frame(read: *mut u8, write: *const u8, len: usize) {
+        let send = |i: usize| {
+            if write.is_null() {
+                DUMMY
+            } else {
+                unsafe { *write.add(i) }
+            }
+        };
+
+        if len == 0 {
+            return;
+        }
+
+        super::discard_stale::<S>();
+
+        while !super::transmit_empty::<S>() {}
+        super::write_data::<S>(send(0));
+
+        for i in 0..len {
+            if i + 1 < len {
+                while !super::transmit_empty::<S>() {}
+                super::write_data::<S>(send(i + 1));
+            }
+
+            while !super::receive_full::<S>() {}
+            let byte = super::read_data::<S>();
+            if !read.is_null() {
+                unsafe { *read.add(i) = byte };
+            }
+        }
+    }
+}
+
+impl<S, SCK, MOSI, MISO> embedded_hal::spi::ErrorType for SpiMaster<S, Polling, SCK, MOSI, MISO> {
+    type Error = Infallible;
+}
+
+impl<S, SCK, MOSI, MISO> embedded_hal::spi::SpiBus for SpiMaster<S, Polling, SCK, MOSI, MISO>
+where
+    S: Spi,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+{
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for word in words {
+            *word = Self::exchange(DUMMY);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for word in words {
+            Self::exchange(*word);
+        }
+
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for i in 0..read.len().max(write.len()) {
+            let byte = Self::exchange(write.get(i).copied().unwrap_or(DUMMY));
+            if let Some(word) = read.get_mut(i) {
+                *word = byte;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        super::discard_stale::<S>();
+
+        for word in words {
+            *word = Self::exchange(*word);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // Every byte is received before the next is sent.
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Several devices on one bus, each with its own [`SpiConfig`] applied at the
+//! start of every transaction.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::Mutex;
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::{ErrorKind, Operation, SpiBus};
+
+use super::{SetConfig, SpiConfig};
+
+/// Possible shared device errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DeviceError<EBUS, ECS> {
+    /// Error with the SPI bus.
+    Spi(EBUS),
+    /// Error setting chip select.
+    Cs(ECS),
+}
+
+impl<EBUS, ECS> embedded_hal_async::spi::Error for DeviceError<EBUS, ECS>
+where
+    EBUS: embedded_hal_async::spi::Error,
+    ECS: core::fmt::Debug,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Spi(e) => e.kind(),
+            Self::Cs(_) => ErrorKind::ChipSelectFault,
+        }
+    }
+}
+
+/// A device with its own chip select and configuration on a bus shared
+/// through an async mutex.
+pub struct SharedDevice<'a, M: RawMutex, BUS, CS, DEL> {
+    bus: &'a Mutex<M, BUS>,
+    cs: CS,
+    delay: DEL,
+    config: SpiConfig,
+}
+
+impl<'a, M: RawMutex, BUS, CS, DEL> SharedDevice<'a, M, BUS, CS, DEL> {
+    pub fn new(bus: &'a Mutex<M, BUS>, cs: CS, delay: DEL, config: SpiConfig) -> Self {
+        Self {
+            bus,
+            cs,
+            delay,
+            config,
+        }
+    }
+
+    /// Change the configuration used from the next transaction.
+    pub fn set_config(&mut self, config: SpiConfig) {
+        self.config = config;
+    }
+
+    pub fn release(self) -> (CS, DEL) {
+        (self.cs, self.delay)
+    }
+}
+
+impl<M: RawMutex, BUS, CS, DEL> embedded_hal_async::spi::ErrorType
+    for SharedDevice<'_, M, BUS, CS, DEL>
+where
+    BUS: embedded_hal_async::spi::ErrorType,
+    CS: OutputPin,
+{
+    type Error = DeviceError<BUS::Error, CS::Error>;
+}
+
+impl<M, BUS, CS, DEL> embedded_hal_async::spi::SpiDevice for SharedDevice<'_, M, BUS, CS, DEL>
+where
+    M: RawMutex,
+    BUS: SpiBus + SetConfig,
+    CS: OutputPin,
+    DEL: embedded_hal_async::delay::DelayNs,
+{
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = self.bus.lock().await;
+
+        bus.set_config(&self.config);
+
+        self.cs.set_low().map_err(DeviceError::Cs)?;
+
+        let result = async {
+            for operation in operations {
+                match operation {
+                    Operation::Read(words) => bus.read(words).await?,
+                    Operation::Write(words) => bus.write(words).await?,
+                    Operation::Transfer(read, write) => bus.transfer(read, write).await?,
+                    Operation::TransferInPlace(words) => bus.transfer_in_place(words).await?,
+                    Operation::DelayNs(ns) => {
+                        bus.flush().await?;
+                        self.delay.delay_ns(*ns).await;
+                    }
+                }
+            }
+            bus.flush().await
+        }
+        .await;
+
+        // Release chip select even after a bus error, so the other devices
+        // on the bus can still be used.
+        let cs = self.cs.set_high();
+
+        result.map_err(DeviceError::Spi)?;
+        cs.map_err(DeviceError::Cs)
+    }
+}
@@ -0,0 +1,357 @@
+//! Slave mode, clocked by an external host. The block only responds while
+//! the host holds slave select low.
+
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embedded_hal::spi::{Mode, Phase, Polarity};
+use woven::{Either, Race};
+
+use super::dma::TransferGuard;
+use super::{BitOrder, Error, MisoPin, MosiPin, SckPin, Spi, SsPin};
+use crate::dma::{Dma, Endpoint};
+use crate::pins::Pin;
+
+/// SPI slave moving data with a transmit and a receive DMA channel.
+///
+/// With [`Phase::CaptureOnFirstTransition`] the host must release slave
+/// select between bytes.
+pub struct SpiSlave<S, T, R, SCK, MOSI, MISO, SS> {
+    spi: S,
+    tx: T,
+    rx: R,
+    sck: SCK,
+    mosi: MOSI,
+    miso: MISO,
+    ss: SS,
+}
+
+impl<S, T, R, SCK, MOSI, MISO, SS> SpiSlave<S, T, R, SCK, MOSI, MISO, SS>
+where
+    S: Spi,
+    T: Dma,
+    R: Dma,
+    SCK: SckPin<S> + Pin,
+    MISO: MisoPin<S> + Pin,
+    MOSI: MosiPin<S> + Pin,
+    SS: SsPin<S> + Pin,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        spi: S,
+        tx_ch: T,
+        rx_ch: R,
+        sck: SCK,
+        mosi: MOSI,
+        miso: MISO,
+        ss: SS,
+        mode: Mode,
+        bit_order: BitOrder,
+    ) -> Self {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        crate::pins::enable_port_clock::<SCK>();
+        crate::pins::enable_port_clock::<MISO>();
+        crate::pins::enable_port_clock::<MOSI>();
+        crate::pins::enable_port_clock::<SS>();
+
+        crate::mux::set_alternate::<SCK>(SCK::ALT);
+        crate::mux::set_alternate::<MISO>(MISO::ALT);
+        crate::mux::set_alternate::<MOSI>(MOSI::ALT);
+        crate::mux::set_alternate::<SS>(SS::ALT);
+
+        // Enable spi clock
+        match S::INDEX {
+            0 => peripherals.sim.scgc4().modify(|_, w| w.spi0()._1()),
+            1 => peripherals.sim.scgc4().modify(|_, w| w.spi1()._1()),
+
+            _ => unreachable!(),
+        }
+
+        crate::dma::set_request::<T>(Some(S::TX_REQUEST));
+        crate::dma::set_request::<R>(Some(S::RX_REQUEST));
+
+        let idle_high = mode.polarity == Polarity::IdleHigh;
+        let second_edge = mode.phase == Phase::CaptureOnSecondTransition;
+        let lsb_first = bit_order == BitOrder::LsbFirst;
+
+        // Enable SPI, slave mode
+        match S::INDEX {
+            0 => {
+                peripherals.spi0.c2().reset();
+                peripherals.spi0.c1().write(|w| {
+                    w.spe()
+                        ._1()
+                        .cpol()
+                        .bit(idle_high)
+                        .cpha()
+                        .bit(second_edge)
+                        .lsbfe()
+                        .bit(lsb_first)
+                });
+            }
+            1 => {
+                peripherals.spi1.c2().reset();
+                peripherals.spi1.c1().write(|w| {
+                    w.spe()
+                        ._1()
+                        .cpol()
+                        .bit(idle_high)
+                        .cpha()
+                        .bit(second_edge)
+                        .lsbfe()
+                        .bit(lsb_first)
+                });
+            }
+
+            _ => unreachable!(),
+        }
+
+        unsafe { cortex_m::peripheral::NVIC::unmask(super::interrupt::interrupt_of::<S>()) };
+
+        Self {
+            spi,
+            tx: tx_ch,
+            rx: rx_ch,
+            sck,
+            mosi,
+            miso,
+            ss,
+        }
+    }
+
+    pub fn release(self) -> (S, T, R, SCK, MOSI, MISO, SS) {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        cortex_m::peripheral::NVIC::mask(super::interrupt::interrupt_of::<S>());
+
+        match S::INDEX {
+            0 => peripherals.spi0.c2().reset(),
+            1 => peripherals.spi1.c2().reset(),
+
+            _ => unreachable!(),
+        }
+
+        (
+            self.spi, self.tx, self.rx, self.sck, self.mosi, self.miso, self.ss,
+        )
+    }
+
+    /// Whether the host is holding slave select low.
+    pub fn is_selected(&self) -> bool {
+        !crate::gpio::data_input::<SS>()
+    }
+
+    /// Receive `read.len()` bytes from the host while sending `write`. Once
+    /// `write` runs out the data shifted out is undefined.
+    ///
+    /// Anything left in the transmit buffer by an earlier transfer is
+    /// discarded, so this should be called while the host is idle.
+    ///
+    /// # Errors
+    /// Returns an error if either DMA channel stopped with an error.
+    ///
+    /// # Panics
+    /// Panics if either buffer is longer than [`MAX_BYTES`](crate::dma::MAX_BYTES).
+    pub async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        assert!(read.len() <= crate::dma::MAX_BYTES);
+        assert!(write.len() <= crate::dma::MAX_BYTES);
+
+        if read.is_empty() {
+            return Ok(());
+        }
+
+        restart::<S>();
+
+        let data_address = data_address::<S>();
+
+        unsafe {
+            if !write.is_empty() {
+                crate::dma::setup_dma_transfer::<T>(&crate::dma::Config {
+                    source: Endpoint::memory(write.as_ptr()),
+                    dest: Endpoint::peripheral(data_address),
+                    bytes: write.len(),
+                    trigger: crate::dma::Trigger::Request,
+                    circular: false,
+                    link: crate::dma::Linking::NONE,
+                });
+            }
+
+            crate::dma::setup_dma_transfer::<R>(&crate::dma::Config {
+                source: Endpoint::peripheral(data_address),
+                dest: Endpoint::memory(read.as_mut_ptr()),
+                bytes: read.len(),
+                trigger: crate::dma::Trigger::Request,
+                circular: false,
+                link: crate::dma::Linking::NONE,
+            });
+        }
+
+        let _guard = TransferGuard::<S, T, R> {
+            _marker: PhantomData,
+        };
+
+        set_dma::<S>(!write.is_empty());
+
+        // The host decides how many bytes are clocked, so only the receive
+        // side is waited on. An unfinished transmit is aborted by the guard.
+        unsafe { crate::dma::wait_dma_transfer::<R>() }
+            .await
+            .map_err(Error::Receive)
+    }
+
+    /// Wait until the host sends `command`, discarding every byte before it.
+    /// The comparison is done by the match register, so the core sleeps
+    /// until it arrives.
+    ///
+    /// The host should leave time after the command for [`Self::transfer`] to
+    /// be started.
+    ///
+    /// # Errors
+    /// Returns an error if the receive DMA channel stopped with an error.
+    pub async fn wait_for_command(&mut self, command: u8) -> Result<(), Error> {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        restart::<S>();
+
+        match S::INDEX {
+            0 => peripherals
+                .spi0
+                .m()
+                .write(|w| unsafe { w.bits_().bits(command) }),
+            1 => peripherals
+                .spi1
+                .m()
+                .write(|w| unsafe { w.bits_().bits(command) }),
+
+            _ => unreachable!(),
+        }
+
+        clear_match::<S>();
+
+        let mut discard = 0_u8;
+
+        loop {
+            // Keep the receive buffer drained so every byte reaches the
+            // match register.
+            unsafe {
+                crate::dma::setup_dma_transfer::<R>(&crate::dma::Config {
+                    source: Endpoint::peripheral(data_address::<S>()),
+                    dest: Endpoint::peripheral(&raw mut discard),
+                    bytes: crate::dma::MAX_BYTES,
+                    trigger: crate::dma::Trigger::Request,
+                    circular: false,
+                    link: crate::dma::Linking::NONE,
+                });
+            }
+
+            let _guard = TransferGuard::<S, T, R> {
+                _marker: PhantomData,
+            };
+
+            set_dma::<S>(false);
+
+            let result = (wait_match::<S>(), unsafe {
+                crate::dma::wait_dma_transfer::<R>()
+            })
+                .race()
+                .await;
+
+            match result {
+                Either::First(()) => return Ok(()),
+                // Drained a full transfer without a match.
+                Either::Second(result) => result.map_err(Error::Receive)?,
+            }
+        }
+    }
+}
+
+fn data_address<S: Spi>() -> *mut u8 {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals.spi0.d().as_ptr(),
+        1 => peripherals.spi1.d().as_ptr(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Briefly disable SPI block `S`, emptying its buffers and clearing its
+/// flags.
+fn restart<S: Spi>() {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => {
+            peripherals.spi0.c1().modify(|_, w| w.spe()._0());
+            peripherals.spi0.c1().modify(|_, w| w.spe()._1());
+        }
+        1 => {
+            peripherals.spi1.c1().modify(|_, w| w.spe()._0());
+            peripherals.spi1.c1().modify(|_, w| w.spe()._1());
+        }
+
+        _ => unreachable!(),
+    }
+}
+
+/// Enable the receive DMA request, and the transmit one if `transmit`.
+fn set_dma<S: Spi>(transmit: bool) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => peripherals
+            .spi0
+            .c2()
+            .modify(|_, w| w.rxdmae()._1().txdmae().bit(transmit)),
+        1 => peripherals
+            .spi1
+            .c2()
+            .modify(|_, w| w.rxdmae()._1().txdmae().bit(transmit)),
+
+        _ => unreachable!(),
+    }
+}
+
+/// SPMF is cleared by writing one after reading it set.
+fn clear_match<S: Spi>() -> bool {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    match S::INDEX {
+        0 => {
+            let matched = peripherals.spi0.s().read().spmf().bit_is_set();
+            peripherals.spi0.s().write(|w| w.spmf()._1());
+            matched
+        }
+        1 => {
+            let matched = peripherals.spi1.s().read().spmf().bit_is_set();
+            peripherals.spi1.s().write(|w| w.spmf()._1());
+            matched
+        }
+
+        _ => unreachable!(),
+    }
+}
+
+async fn wait_match<S: Spi>() {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    core::future::poll_fn(|cx| {
+        super::interrupt::WAKERS[S::INDEX as usize].register(cx.waker());
+
+        if clear_match::<S>() {
+            Poll::Ready(())
+        } else {
+            match S::INDEX {
+                0 => peripherals.spi0.c2().modify(|_, w| w.spmie()._1()),
+                1 => peripherals.spi1.c2().modify(|_, w| w.spmie()._1()),
+
+                _ => unreachable!(),
+            }
+
+            Poll::Pending
+        }
+    })
+    .await;
+}
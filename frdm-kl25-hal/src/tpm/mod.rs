@@ -1,5 +1,17 @@
+pub mod periodic;
 pub mod pwm;
 
+use core::cell::Cell;
+
+use cortex_m_rt::interrupt;
+use critical_section::Mutex;
+use pac::Interrupt as interrupt;
+
+/// Called from the interrupt of each timer, and responsible for clearing the
+/// flags that raised it.
+#[allow(clippy::type_complexity)]
+static HANDLERS: Mutex<[Cell<Option<fn()>>; 3]> = Mutex::new([const { Cell::new(None) }; 3]);
+
 mod sealed {
     pub trait Sealed {}
 }
@@ -107,7 +119,7 @@ t_pin!(PTD3, Tpm0, 3, Alt4);
 t_pin!(PTD4, Tpm0, 4, Alt4);
 t_pin!(PTD5, Tpm0, 5, Alt4);
 
-fn ensure_clock_active<T: Timer>() {
+pub(crate) fn ensure_clock_active<T: Timer>() {
     let peripherals = unsafe { pac::Peripherals::steal() };
 
     peripherals.sim.scgc6().modify(|_, w| match T::INDEX {
@@ -119,7 +131,7 @@ fn ensure_clock_active<T: Timer>() {
     });
 }
 
-fn enable_timer<T: Timer>(enable: bool) {
+pub(crate) fn enable_timer<T: Timer>(enable: bool) {
     let peripherals = unsafe { pac::Peripherals::steal() };
 
     match T::INDEX {
@@ -149,7 +161,7 @@ fn enable_timer<T: Timer>(enable: bool) {
     };
 }
 
-fn set_timer_mod_value<T: Timer>(val: u16) {
+pub(crate) fn set_timer_mod_value<T: Timer>(val: u16) {
     let peripherals = unsafe { pac::Peripherals::steal() };
 
     unsafe {
@@ -163,6 +175,96 @@ fn set_timer_mod_value<T: Timer>(val: u16) {
     }
 }
 
+/// Start counting with the counter clock divided by `1 << prescale`, raising
+/// the timer's interrupt on each overflow.
+pub(crate) fn start_with_overflow_interrupt<T: Timer>(prescale: u8) {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    unsafe {
+        match T::INDEX {
+            0 => peripherals
+                .tpm0
+                .sc()
+                .write(|w| w.ps().bits(prescale).toie()._1().cmod()._01()),
+            1 => peripherals
+                .tpm1
+                .sc()
+                .write(|w| w.ps().bits(prescale).toie()._1().cmod()._01()),
+            2 => peripherals
+                .tpm2
+                .sc()
+                .write(|w| w.ps().bits(prescale).toie()._1().cmod()._01()),
+
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Clear the overflow flag, returning whether it was set.
+pub(crate) fn take_overflow(index: u32) -> bool {
+    let peripherals = unsafe { pac::Peripherals::steal() };
+
+    let overflowed = match index {
+        0 => peripherals.tpm0.sc().read().tof().bit_is_set(),
+        1 => peripherals.tpm1.sc().read().tof().bit_is_set(),
+        2 => peripherals.tpm2.sc().read().tof().bit_is_set(),
+
+        _ => unreachable!(),
+    };
+
+    // TOF is cleared by writing one.
+    if overflowed {
+        match index {
+            0 => peripherals.tpm0.sc().modify(|_, w| w.tof()._1()),
+            1 => peripherals.tpm1.sc().modify(|_, w| w.tof()._1()),
+            2 => peripherals.tpm2.sc().modify(|_, w| w.tof()._1()),
+
+            _ => unreachable!(),
+        }
+    }
+
+    overflowed
+}
+
+/// Route the timer's interrupt to `handler`, or mask it if `None`.
+pub(crate) fn set_interrupt_handler<T: Timer>(handler: Option<fn()>) {
+    critical_section::with(|cs| HANDLERS.borrow(cs)[T::INDEX as usize].set(handler));
+
+    let interrupt = match T::INDEX {
+        0 => pac::Interrupt::TPM0,
+        1 => pac::Interrupt::TPM1,
+        2 => pac::Interrupt::TPM2,
+
+        _ => unreachable!(),
+    };
+
+    match handler {
+        Some(_) => unsafe { cortex_m::peripheral::NVIC::unmask(interrupt) },
+        None => cortex_m::peripheral::NVIC::mask(interrupt),
+    }
+}
+
+fn on_interrupt(index: usize) {
+    if let Some(handler) = critical_section::with(|cs| HANDLERS.borrow(cs)[index].get()) {
+        handler();
+    }
+}
+
+#[interrupt]
+fn TPM0() {
+    on_interrupt(0);
+}
+
+#[interrupt]
+fn TPM1() {
+    on_interrupt(1);
+}
+
+#[interrupt]
+fn TPM2() {
+    on_interrupt(2);
+}
+
 fn disable_channel<T: Timer, const CHANNEL: u32>() {
     let peripherals = unsafe { pac::Peripherals::steal() };
 
@@ -0,0 +1,77 @@
+//! Overflow interrupts at a fixed rate, for work that must keep its timing
+//! whatever the executor is doing, such as software PWM.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use super::Timer;
+use crate::clock::Clocks;
+
+/// Called on each overflow of each timer.
+#[allow(clippy::type_complexity)]
+static HANDLERS: Mutex<[Cell<Option<fn()>>; 3]> = Mutex::new([const { Cell::new(None) }; 3]);
+
+/// Timer calling a handler from its overflow interrupt.
+pub struct Periodic<T> {
+    timer: T,
+    frequency_hz: u32,
+}
+
+impl<T> Periodic<T>
+where
+    T: Timer,
+{
+    /// Call `handler` from the timer's interrupt at the achievable rate
+    /// closest to `frequency_hz`, see [`Self::frequency_hz`].
+    ///
+    /// # Panics
+    /// Panics if `frequency_hz` is zero, above the peripheral clock, or too low
+    /// to reach with the largest prescaler.
+    pub fn new(timer: T, frequency_hz: u32, clocks: &Clocks, handler: fn()) -> Self {
+        assert!(frequency_hz > 0 && frequency_hz <= clocks.peripheral_hz());
+
+        let ticks = clocks.peripheral_hz() / frequency_hz;
+        let prescale = (0..=7)
+            .find(|prescale| ticks >> prescale <= 1 << 16)
+            .expect("frequency too low");
+        let modulo = (ticks >> prescale) - 1;
+
+        critical_section::with(|cs| HANDLERS.borrow(cs)[T::INDEX as usize].set(Some(handler)));
+
+        super::ensure_clock_active::<T>();
+        super::enable_timer::<T>(false);
+        super::set_timer_mod_value::<T>(u16::try_from(modulo).unwrap());
+        super::set_interrupt_handler::<T>(Some(on_overflow::<T>));
+        super::start_with_overflow_interrupt::<T>(prescale);
+
+        Self {
+            timer,
+            frequency_hz: clocks.peripheral_hz() / ((modulo + 1) << prescale),
+        }
+    }
+
+    /// Rate the handler is called at. Units of Hertz.
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz
+    }
+
+    /// Stop the timer and its interrupt.
+    pub fn release(self) -> T {
+        super::set_interrupt_handler::<T>(None);
+        super::enable_timer::<T>(false);
+
+        critical_section::with(|cs| HANDLERS.borrow(cs)[T::INDEX as usize].set(None));
+
+        self.timer
+    }
+}
+
+fn on_overflow<T: Timer>() {
+    super::take_overflow(T::INDEX);
+
+    if let Some(handler) = critical_section::with(|cs| HANDLERS.borrow(cs)[T::INDEX as usize].get())
+    {
+        handler();
+    }
+}
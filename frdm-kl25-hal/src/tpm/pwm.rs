@@ -3,20 +3,24 @@
 use core::marker::PhantomData;
 
 use super::{Timer, TimerPin, Tpm0, Tpm1, Tpm2};
+use crate::clock::Clocks;
 use crate::pins::Pin;
 
 pub struct Pwm<T> {
     _timer: T,
+    frequency_hz: u32,
 }
 
 impl<T> Pwm<T>
 where
     T: Timer,
 {
-    pub fn new(timer: T) -> Self {
+    pub fn new(timer: T, clocks: &Clocks) -> Self {
+        const MOD: u16 = u16::MAX - 1;
+
         super::ensure_clock_active::<T>();
         super::enable_timer::<T>(false);
-        super::set_timer_mod_value::<T>(u16::MAX - 1);
+        super::set_timer_mod_value::<T>(MOD);
         match T::INDEX {
             0 => {
                 super::disable_channel::<T, 0>();
@@ -35,7 +39,15 @@ where
         }
         super::enable_timer::<T>(true);
 
-        Self { _timer: timer }
+        Self {
+            _timer: timer,
+            frequency_hz: clocks.peripheral_hz() / (u32::from(MOD) + 1),
+        }
+    }
+
+    /// PWM period frequency. Units of Hertz.
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz
     }
 }
 
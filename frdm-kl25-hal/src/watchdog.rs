@@ -0,0 +1,159 @@
+//! Computer operating properly watchdog. It runs from reset, timing out
+//! after about a second, until either disabled or configured.
+
+use crate::clock::Clocks;
+
+/// Low power oscillator frequency. Units of Hertz.
+const LPO_HZ: u32 = 1_000;
+
+/// Clock counted by the watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ClockSource {
+    /// The 1 kHz low power oscillator, which keeps running if the MCG fails.
+    Lpo,
+    /// The bus clock.
+    Bus,
+}
+
+/// Clock cycles before the watchdog resets the chip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Timeout {
+    /// 2^5 LPO or 2^13 bus cycles.
+    Short,
+    /// 2^8 LPO or 2^16 bus cycles.
+    Medium,
+    /// 2^10 LPO or 2^18 bus cycles.
+    Long,
+}
+
+/// Watchdog configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    pub timeout: Timeout,
+    pub clock: ClockSource,
+    /// Reset if fed before the last quarter of the timeout. Only available
+    /// with [`ClockSource::Bus`].
+    pub windowed: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Timeout::Long,
+            clock: ClockSource::Lpo,
+            windowed: false,
+        }
+    }
+}
+
+/// Ownership of the COP watchdog. Its control register can only be written
+/// once after reset.
+pub struct Cop {
+    _private: (),
+}
+
+impl Cop {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Stop the watchdog until the next reset.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn disable(self) {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        peripherals.sim.copc().write(|w| w.copt()._00());
+    }
+
+    /// Reconfigure the watchdog with `config`. It must then be fed with
+    /// [`Watchdog::feed`] until the next reset.
+    ///
+    /// # Panics
+    /// Panics if windowed mode is requested with the LPO clock.
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn enable(self, config: &WatchdogConfig, clocks: &Clocks) -> Watchdog {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        assert!(
+            !config.windowed || config.clock == ClockSource::Bus,
+            "windowed mode needs the bus clock"
+        );
+
+        peripherals.sim.copc().write(|w| {
+            match config.timeout {
+                Timeout::Short => w.copt()._01(),
+                Timeout::Medium => w.copt()._10(),
+                Timeout::Long => w.copt()._11(),
+            };
+
+            w.copclks()
+                .bit(config.clock == ClockSource::Bus)
+                .copw()
+                .bit(config.windowed)
+        });
+
+        let (lpo_cycles, bus_cycles): (u32, u32) = match config.timeout {
+            Timeout::Short => (1 << 5, 1 << 13),
+            Timeout::Medium => (1 << 8, 1 << 16),
+            Timeout::Long => (1 << 10, 1 << 18),
+        };
+
+        let timeout_us = match config.clock {
+            ClockSource::Lpo => lpo_cycles * (1_000_000 / LPO_HZ),
+            ClockSource::Bus => {
+                let us = u64::from(bus_cycles) * 1_000_000 / u64::from(clocks.bus_hz());
+                u32::try_from(us).unwrap_or(u32::MAX)
+            }
+        };
+
+        Watchdog {
+            timeout_us,
+            windowed: config.windowed,
+        }
+    }
+}
+
+/// Running watchdog, which resets the chip unless fed within its timeout.
+pub struct Watchdog {
+    timeout_us: u32,
+    windowed: bool,
+}
+
+impl Watchdog {
+    /// Restart the timeout. In windowed mode, feeding before the last
+    /// quarter of the timeout resets the chip.
+    pub fn feed(&mut self) {
+        let peripherals = unsafe { pac::Peripherals::steal() };
+
+        // Anything written between the two values resets the chip.
+        critical_section::with(|_| {
+            peripherals
+                .sim
+                .srvcop()
+                .write(|w| unsafe { w.srvcop().bits(0x55) });
+            peripherals
+                .sim
+                .srvcop()
+                .write(|w| unsafe { w.srvcop().bits(0xAA) });
+        });
+    }
+
+    /// Time without feeding before a reset. Approximate with the LPO clock.
+    /// Units of microseconds.
+    #[must_use]
+    pub fn timeout_us(&self) -> u32 {
+        self.timeout_us
+    }
+
+    /// Time after feeding before the window opens, zero if not windowed.
+    /// Units of microseconds.
+    #[must_use]
+    pub fn window_us(&self) -> u32 {
+        if self.windowed {
+            self.timeout_us / 4 * 3
+        } else {
+            0
+        }
+    }
+}
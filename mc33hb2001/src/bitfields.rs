@@ -1,6 +1,11 @@
 /// Current limit values. Units of Amperes.
 #[repr(u8)]
-#[derive(Debug, defmt::Format)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum CurrentLimit {
     /// Limit to 5.4A.
     Lim5_4 = 0b00,
@@ -30,7 +35,12 @@ impl CurrentLimit {
 
 /// Slew rate values. Units of volts per microsecond.
 #[repr(u8)]
-#[derive(Debug, defmt::Format)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum SlewRate {
     /// Bypass slew rate control.
     Bypass = 0b000,
@@ -72,7 +82,12 @@ impl SlewRate {
 
 /// Input bridge mode.
 #[repr(u8)]
-#[derive(Debug, defmt::Format)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum BridgeMode {
     /// Half-bridge control mode.
     HalfBridge = 0b0,
@@ -96,7 +111,12 @@ impl BridgeMode {
 
 /// Input control mode.
 #[repr(u8)]
-#[derive(Debug, defmt::Format)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum ControlMode {
     /// Parallel pin control, SPI virtual inputs disabled.
     Parallel = 0b0,
@@ -120,7 +140,12 @@ impl ControlMode {
 
 /// The logic value of a driver input
 #[repr(u8)]
-#[derive(Debug, defmt::Format)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum Input {
     /// Logic low.
     Low = 0b0,
@@ -144,6 +169,12 @@ impl Input {
 
 /// Configuration and control register.
 #[bitfield_struct::bitfield(u16, defmt = true, order = Msb)]
+#[derive(PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub struct Configuration {
     #[bits(3)]
     __: u8,
@@ -197,6 +228,12 @@ pub struct Configuration {
 
 /// Status register.
 #[bitfield_struct::bitfield(u16, defmt = true, order = Msb)]
+#[derive(PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub struct Status {
     #[bits(4)]
     __: u8,
@@ -250,8 +287,31 @@ pub struct Status {
     pub overtemperature_shutdown: bool,
 }
 
+impl Status {
+    /// Returns true if a fault that disables the outputs has occurred. This
+    /// covers short-circuits, over-temperature shutdown and supply faults, but
+    /// not warnings, current limiting, open load or SPI framing errors.
+    #[must_use]
+    pub const fn is_critical(&self) -> bool {
+        self.charge_pump_overvoltage()
+            || self.vpwr_undervoltage()
+            || self.vpwr_overvoltage()
+            || self.sc_power_output_2()
+            || self.sc_power_output_1()
+            || self.sc_ground_output_2()
+            || self.sc_ground_output_1()
+            || self.overtemperature_shutdown()
+    }
+}
+
 /// Status mask register.
 #[bitfield_struct::bitfield(u16, defmt = true, order = Msb)]
+#[derive(PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub struct StatusMask {
     #[bits(3)]
     __: u8,
@@ -309,3 +369,174 @@ pub struct StatusMask {
     #[bits(1)]
     pub overtemperature_shutdown: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn current_limit() -> impl Strategy<Value = CurrentLimit> {
+        (0_u8..=0b11).prop_map(CurrentLimit::from_bits)
+    }
+
+    fn slew_rate() -> impl Strategy<Value = SlewRate> {
+        (0_u8..=0b111).prop_map(SlewRate::from_bits)
+    }
+
+    fn bridge_mode() -> impl Strategy<Value = BridgeMode> {
+        (0_u8..=0b1).prop_map(BridgeMode::from_bits)
+    }
+
+    fn control_mode() -> impl Strategy<Value = ControlMode> {
+        (0_u8..=0b1).prop_map(ControlMode::from_bits)
+    }
+
+    fn input() -> impl Strategy<Value = Input> {
+        (0_u8..=0b1).prop_map(Input::from_bits)
+    }
+
+    #[test]
+    fn enums_round_trip() {
+        for bits in 0..=0b11 {
+            assert_eq!(CurrentLimit::from_bits(bits).into_bits(), bits);
+        }
+        for bits in 0..=0b111 {
+            assert_eq!(SlewRate::from_bits(bits).into_bits(), bits);
+        }
+        for bits in 0..=0b1 {
+            assert_eq!(BridgeMode::from_bits(bits).into_bits(), bits);
+            assert_eq!(ControlMode::from_bits(bits).into_bits(), bits);
+            assert_eq!(Input::from_bits(bits).into_bits(), bits);
+        }
+    }
+
+    #[test]
+    fn default_configuration_matches_reset_value() {
+        assert_eq!(
+            Configuration::new().into_bits(),
+            crate::DEFAULT_CONFIGURATION
+        );
+    }
+
+    #[test]
+    fn ident_fits_in_frame_data() {
+        assert_eq!(crate::IDENT & !0b0001_1111_1111_1111, 0);
+    }
+
+    #[test]
+    fn configuration_bit_positions() {
+        let bit = |c: Configuration| c.into_bits();
+        let empty = Configuration::from_bits(0);
+
+        assert_eq!(bit(empty.with_check_for_open_load(true)), 1 << 12);
+        assert_eq!(bit(empty.with_enable_thermal_management(true)), 1 << 11);
+        assert_eq!(bit(empty.with_enable_active_current_limit(true)), 1 << 10);
+        assert_eq!(
+            bit(empty.with_current_limit(CurrentLimit::Lim10_7)),
+            0b11 << 8
+        );
+        assert_eq!(bit(empty.with_slew_rate(SlewRate::Sr0_25)), 0b111 << 5);
+        assert_eq!(bit(empty.with_enable(true)), 1 << 4);
+        assert_eq!(bit(empty.with_bridge_mode(BridgeMode::HBridge)), 1 << 3);
+        assert_eq!(bit(empty.with_control_mode(ControlMode::Spi)), 1 << 2);
+        assert_eq!(bit(empty.with_virtual_input_2(Input::High)), 1 << 1);
+        assert_eq!(bit(empty.with_virtual_input_1(Input::High)), 1 << 0);
+    }
+
+    #[test]
+    fn status_bit_positions() {
+        let bit = |s: Status| s.into_bits();
+        let empty = Status::from_bits(0);
+
+        assert_eq!(bit(empty.with_spi_framing_error(true)), 1 << 11);
+        assert_eq!(bit(empty.with_charge_pump_overvoltage(true)), 1 << 10);
+        assert_eq!(bit(empty.with_vpwr_undervoltage(true)), 1 << 9);
+        assert_eq!(bit(empty.with_vpwr_overvoltage(true)), 1 << 8);
+        assert_eq!(bit(empty.with_sc_power_output_2(true)), 1 << 7);
+        assert_eq!(bit(empty.with_sc_power_output_1(true)), 1 << 6);
+        assert_eq!(bit(empty.with_sc_ground_output_2(true)), 1 << 5);
+        assert_eq!(bit(empty.with_sc_ground_output_1(true)), 1 << 4);
+        assert_eq!(bit(empty.with_open_load(true)), 1 << 3);
+        assert_eq!(bit(empty.with_overcurrent(true)), 1 << 2);
+        assert_eq!(bit(empty.with_thermal_warning(true)), 1 << 1);
+        assert_eq!(bit(empty.with_overtemperature_shutdown(true)), 1 << 0);
+    }
+
+    #[test]
+    fn status_mask_bit_positions() {
+        let bit = |s: StatusMask| s.into_bits();
+        let empty = StatusMask::from_bits(0);
+
+        assert_eq!(bit(empty.with_disable_overvoltage(true)), 1 << 12);
+        assert_eq!(bit(empty.with_spi_framing_error(true)), 1 << 11);
+        assert_eq!(bit(empty.with_charge_pump_overvoltage(true)), 1 << 10);
+        assert_eq!(bit(empty.with_vpwr_undervoltage(true)), 1 << 9);
+        assert_eq!(bit(empty.with_vpwr_overvoltage(true)), 1 << 8);
+        assert_eq!(bit(empty.with_sc_power_output_2(true)), 1 << 7);
+        assert_eq!(bit(empty.with_sc_power_output_1(true)), 1 << 6);
+        assert_eq!(bit(empty.with_sc_ground_output_2(true)), 1 << 5);
+        assert_eq!(bit(empty.with_sc_ground_output_1(true)), 1 << 4);
+        assert_eq!(bit(empty.with_open_load(true)), 1 << 3);
+        assert_eq!(bit(empty.with_overcurrent(true)), 1 << 2);
+        assert_eq!(bit(empty.with_thermal_warning(true)), 1 << 1);
+        assert_eq!(bit(empty.with_overtemperature_shutdown(true)), 1 << 0);
+    }
+
+    proptest! {
+        #[test]
+        fn configuration_round_trips_fields(
+            check_for_open_load: bool,
+            enable_thermal_management: bool,
+            enable_active_current_limit: bool,
+            current_limit in current_limit(),
+            slew_rate in slew_rate(),
+            enable: bool,
+            bridge_mode in bridge_mode(),
+            control_mode in control_mode(),
+            virtual_input_2 in input(),
+            virtual_input_1 in input(),
+        ) {
+            let c = Configuration::from_bits(0)
+                .with_check_for_open_load(check_for_open_load)
+                .with_enable_thermal_management(enable_thermal_management)
+                .with_enable_active_current_limit(enable_active_current_limit)
+                .with_current_limit(current_limit)
+                .with_slew_rate(slew_rate)
+                .with_enable(enable)
+                .with_bridge_mode(bridge_mode)
+                .with_control_mode(control_mode)
+                .with_virtual_input_2(virtual_input_2)
+                .with_virtual_input_1(virtual_input_1);
+
+            let c = Configuration::from_bits(c.into_bits());
+
+            prop_assert_eq!(c.check_for_open_load(), check_for_open_load);
+            prop_assert_eq!(c.enable_thermal_management(), enable_thermal_management);
+            prop_assert_eq!(c.enable_active_current_limit(), enable_active_current_limit);
+            prop_assert_eq!(c.current_limit(), current_limit);
+            prop_assert_eq!(c.slew_rate(), slew_rate);
+            prop_assert_eq!(c.enable(), enable);
+            prop_assert_eq!(c.bridge_mode(), bridge_mode);
+            prop_assert_eq!(c.control_mode(), control_mode);
+            prop_assert_eq!(c.virtual_input_2(), virtual_input_2);
+            prop_assert_eq!(c.virtual_input_1(), virtual_input_1);
+            prop_assert_eq!(c.into_bits() & !0b0001_1111_1111_1111, 0);
+        }
+
+        #[test]
+        fn configuration_round_trips_bits(bits in 0_u16..=0b0001_1111_1111_1111) {
+            prop_assert_eq!(Configuration::from_bits(bits).into_bits(), bits);
+        }
+
+        #[test]
+        fn status_round_trips_bits(bits in 0_u16..=0b0000_1111_1111_1111) {
+            prop_assert_eq!(Status::from_bits(bits).into_bits(), bits);
+        }
+
+        #[test]
+        fn status_mask_round_trips_bits(bits in 0_u16..=0b0001_1111_1111_1111) {
+            prop_assert_eq!(StatusMask::from_bits(bits).into_bits(), bits);
+        }
+    }
+}
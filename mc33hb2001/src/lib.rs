@@ -5,8 +5,18 @@
 pub use bitfields::{
     BridgeMode, Configuration, ControlMode, CurrentLimit, Input, SlewRate, Status, StatusMask,
 };
+pub use short_test::{OutputShorts, ShortTest};
+pub use validation::Violations;
+
+pub mod manager;
+pub mod shared;
+pub mod soft_pwm;
+pub mod solenoid;
+pub mod stepper;
 
 mod bitfields;
+mod short_test;
+mod validation;
 
 const IDENT: u16 = 0b0000_0000_0000_0010;
 const DEFAULT_CONFIGURATION: u16 = 0b0000_1101_1001_1000;
@@ -24,6 +34,17 @@ pub enum Setup<ESPI, EEN, EDIS> {
     IncorrectIdent(u16),
     /// Failed to correctly read-back a modified register.
     CouldNotModifyRegisters,
+    /// An output is short-circuited to ground or VPWR.
+    ShortCircuit(ShortTest),
+}
+
+/// Possible checked configuration errors.
+#[derive(Debug, defmt::Format)]
+pub enum Configure<ESPI> {
+    /// Error with the SPI bus.
+    Spi(ESPI),
+    /// The configuration broke one or more validation rules.
+    Invalid(Violations),
 }
 
 /// MC33HB2001 SPI driver.
@@ -65,6 +86,29 @@ where
         Ok(this)
     }
 
+    /// Creates a new MC33HB2001 Driver like [`Driver::new`], then runs
+    /// [`Driver::short_test`] so the load is only driven normally if neither
+    /// output is shorted.
+    ///
+    /// # Errors
+    /// Returns an error if the driver could not be set up, or if an output is
+    /// short-circuited.
+    pub async fn new_tested(
+        spi: SPI,
+        enable: EN,
+        disable: DIS,
+        delay: DEL,
+    ) -> Result<Self, Setup<ESPI, EEN, EDIS>> {
+        let mut this = Self::new(spi, enable, disable, delay).await?;
+
+        let test = this.short_test().await.map_err(Setup::Spi)?;
+        if !test.passed() {
+            return Err(Setup::ShortCircuit(test));
+        }
+
+        Ok(this)
+    }
+
     async fn setup(&mut self) -> Result<(), Setup<ESPI, EEN, EDIS>> {
         self.disable.set_low().map_err(Setup::Disable)?;
         self.enable.set_high().map_err(Setup::Enable)?;
@@ -134,6 +178,24 @@ where
         self.write(Register::ConfigAndControl, c.into_bits()).await
     }
 
+    /// Set the configuration and control register content, refusing to send
+    /// configurations that fail [`Configuration::validate`].
+    ///
+    /// # Errors
+    /// Returns an error if the configuration is invalid, and propagates errors
+    /// from the SPI bus.
+    pub async fn set_configuration_checked(
+        &mut self,
+        c: Configuration,
+    ) -> Result<(), Configure<ESPI>> {
+        let violations = c.validate();
+        if !violations.is_empty() {
+            return Err(Configure::Invalid(violations));
+        }
+
+        self.set_configuration(c).await.map_err(Configure::Spi)
+    }
+
     /// Get the status register content.
     ///
     /// # Errors
@@ -165,6 +227,13 @@ where
     pub async fn set_status_mask(&mut self, c: StatusMask) -> Result<(), ESPI> {
         self.write(Register::FaultStatusMask, c.into_bits()).await
     }
+
+    /// Release the SPI device, enable and disable pins, and delay
+    /// implementation. The pins are left as they are, so the outputs stay in
+    /// their current state.
+    pub fn release(self) -> (SPI, EN, DIS, DEL) {
+        (self.spi, self.enable, self.disable, self.delay)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -176,17 +245,23 @@ enum Register {
     ConfigAndControl = 0b0110_0000_0000_0000,
 }
 
+const fn write_frame(register: Register, mut data: u16) -> [u8; 2] {
+    data &= 0b0001_1111_1111_1111;
+    data |= 0b1000_0000_0000_0000; // Write operation
+    data |= register as u16;
+    data.to_be_bytes()
+}
+
 impl<SPI, EN, DIS, DEL, ESPI> Driver<SPI, EN, DIS, DEL>
 where
     SPI: embedded_hal_async::spi::SpiDevice<Error = ESPI>,
 {
-    async fn write(&mut self, register: Register, mut data: u16) -> Result<(), ESPI> {
-        data &= 0b0001_1111_1111_1111;
-        data |= 0b1000_0000_0000_0000; // Write operation
-        data |= register as u16;
+    async fn write(&mut self, register: Register, data: u16) -> Result<(), ESPI> {
         // Workaround because responses are delayed by one CS cycle.
         let mut buf = [0_u8; 2];
-        self.spi.transfer(&mut buf, &data.to_be_bytes()).await?;
+        self.spi
+            .transfer(&mut buf, &write_frame(register, data))
+            .await?;
         Ok(())
     }
 
@@ -199,3 +274,151 @@ where
         Ok(u16::from_be_bytes(buf) & 0b0001_1111_1111_1111)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use embedded_hal_async::spi::Operation;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Records written frames and answers with queued responses.
+    #[derive(Default)]
+    struct MockSpi {
+        written: Vec<u16>,
+        responses: Vec<u16>,
+    }
+
+    impl embedded_hal_async::spi::ErrorType for MockSpi {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal_async::spi::SpiDevice for MockSpi {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            for operation in operations {
+                let Operation::Transfer(read, write) = operation else { unimplemented!() };
+
+                self.written.push(u16::from_be_bytes([write[0], write[1]]));
+                let response = if self.responses.is_empty() {
+                    0
+                } else {
+                    self.responses.remove(0)
+                };
+                read.copy_from_slice(&response.to_be_bytes());
+            }
+            Ok(())
+        }
+    }
+
+    /// Pin and delay that do nothing.
+    struct Noop;
+
+    impl embedded_hal::digital::ErrorType for Noop {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal::digital::OutputPin for Noop {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl embedded_hal_async::delay::DelayNs for Noop {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    fn driver(responses: Vec<u16>) -> Driver<MockSpi, Noop, Noop, Noop> {
+        Driver {
+            spi: MockSpi {
+                written: Vec::new(),
+                responses,
+            },
+            enable: Noop,
+            disable: Noop,
+            delay: Noop,
+        }
+    }
+
+    fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut cx = core::task::Context::from_waker(core::task::Waker::noop());
+        loop {
+            if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn read_masks_ident() {
+        let mut driver = driver(std::vec![0xFFFF, 0b1110_0000_0000_0000 | IDENT]);
+        let Ok(ident) = block_on(driver.read(Register::Identification));
+        assert_eq!(ident, IDENT);
+        assert_eq!(driver.spi.written, [Register::Identification as u16, 0]);
+    }
+
+    #[test]
+    fn checked_rejects_invalid_without_sending() {
+        let mut driver = driver(Vec::new());
+        let invalid = Configuration::new().with_enable_active_current_limit(false);
+
+        let result = block_on(driver.set_configuration_checked(invalid));
+        assert!(
+            matches!(result, Err(Configure::Invalid(violations)) if violations == invalid.validate())
+        );
+        assert!(driver.spi.written.is_empty());
+    }
+
+    #[test]
+    fn checked_sends_valid() {
+        let mut driver = driver(Vec::new());
+        let valid = Configuration::new();
+
+        assert!(block_on(driver.set_configuration_checked(valid)).is_ok());
+        assert_eq!(
+            driver.spi.written,
+            [u16::from_be_bytes(write_frame(
+                Register::ConfigAndControl,
+                valid.into_bits()
+            ))]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn write_masks_data(data: u16) {
+            for register in [
+                Register::Identification,
+                Register::Status,
+                Register::FaultStatusMask,
+                Register::ConfigAndControl,
+            ] {
+                let mut driver = driver(Vec::new());
+                let Ok(()) = block_on(driver.write(register, data));
+                prop_assert_eq!(
+                    &driver.spi.written,
+                    &[0b1000_0000_0000_0000 | register as u16 | (data & 0b0001_1111_1111_1111)]
+                );
+            }
+        }
+
+        #[test]
+        fn read_masks_response(response: u16) {
+            let mut driver = driver(std::vec![0, response]);
+            let Ok(value) = block_on(driver.read(Register::Status));
+            prop_assert_eq!(value, response & 0b0001_1111_1111_1111);
+            prop_assert_eq!(&driver.spi.written, &[Register::Status as u16, 0]);
+        }
+    }
+}
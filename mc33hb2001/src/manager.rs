@@ -0,0 +1,162 @@
+//! Coordinated control of several MC33HB2001 bridges, for example sharing one
+//! SPI bus with separate chip selects.
+
+use crate::{Configuration, Driver, Status};
+
+/// Possible manager errors. Bridges are identified by their index.
+#[derive(Debug, defmt::Format)]
+pub enum Error<ESPI> {
+    /// Error with the SPI bus of a bridge.
+    Spi(usize, ESPI),
+}
+
+/// Bridges found untrustworthy by [`Manager::supervise`]. All bridges have been
+/// disabled.
+#[derive(Debug, defmt::Format)]
+pub struct Tripped<ESPI, const N: usize> {
+    /// Status of every bridge, or the error reading it.
+    pub statuses: [Result<Status, ESPI>; N],
+    /// Result of disabling the bridges.
+    pub disabled: Result<(), Error<ESPI>>,
+}
+
+/// Owns N bridges and keeps them in step with each other.
+pub struct Manager<SPI, EN, DIS, DEL, const N: usize> {
+    drivers: [Driver<SPI, EN, DIS, DEL>; N],
+    configuration: Configuration,
+}
+
+impl<SPI, EN, DIS, DEL, ESPI, EEN, EDIS, const N: usize> Manager<SPI, EN, DIS, DEL, N>
+where
+    SPI: embedded_hal_async::spi::SpiDevice<Error = ESPI>,
+    EN: embedded_hal::digital::OutputPin<Error = EEN>,
+    DIS: embedded_hal::digital::OutputPin<Error = EDIS>,
+    DEL: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new manager from already set up bridges, broadcasting
+    /// `configuration` to all of them with outputs disabled.
+    ///
+    /// # Errors
+    /// Propagates errors from any bridge.
+    pub async fn new(
+        drivers: [Driver<SPI, EN, DIS, DEL>; N],
+        configuration: Configuration,
+    ) -> Result<Self, Error<ESPI>> {
+        let mut this = Self {
+            drivers,
+            configuration: configuration.with_enable(false),
+        };
+
+        this.broadcast(this.configuration).await?;
+
+        Ok(this)
+    }
+
+    /// Last configuration broadcast to all bridges.
+    pub fn configuration(&self) -> Configuration {
+        self.configuration
+    }
+
+    /// Set the configuration and control register content of every bridge.
+    ///
+    /// # Errors
+    /// Propagates the first error from any bridge. Remaining bridges are still
+    /// written to.
+    pub async fn broadcast(&mut self, c: Configuration) -> Result<(), Error<ESPI>> {
+        self.configuration = c;
+
+        let mut result = Ok(());
+        for (i, driver) in self.drivers.iter_mut().enumerate() {
+            if let Err(e) = driver.set_configuration(c).await {
+                result = result.and(Err(Error::Spi(i, e)));
+            }
+        }
+
+        result
+    }
+
+    /// Enable the outputs of every bridge, back to back.
+    ///
+    /// # Errors
+    /// Propagates errors from any bridge. If any bridge could not be enabled,
+    /// all bridges are disabled again.
+    pub async fn enable_all(&mut self) -> Result<(), Error<ESPI>> {
+        let result = self.broadcast(self.configuration.with_enable(true)).await;
+
+        if result.is_err() {
+            // The bridge that failed to enable is likely to fail again, and
+            // `result` already names it.
+            let _ = self.disable_all().await;
+        }
+
+        result
+    }
+
+    /// Disable the outputs of every bridge, back to back.
+    ///
+    /// # Errors
+    /// Propagates the first error from any bridge. Remaining bridges are still
+    /// disabled.
+    pub async fn disable_all(&mut self) -> Result<(), Error<ESPI>> {
+        self.broadcast(self.configuration.with_enable(false)).await
+    }
+
+    /// Get the status register content of every bridge, or the error reading
+    /// it. Every bridge is read, whatever the others return.
+    pub async fn faults(&mut self) -> [Result<Status, ESPI>; N] {
+        let mut statuses = [const { None }; N];
+
+        for (driver, status) in self.drivers.iter_mut().zip(&mut statuses) {
+            *status = Some(driver.status().await);
+        }
+
+        // Every bridge was read above.
+        statuses.map(|status| status.unwrap_or_else(|| unreachable!()))
+    }
+
+    /// Get the status register content of every bridge, disabling all bridges
+    /// if any of them reports a critical fault, see [`Status::is_critical`], or
+    /// can't be read.
+    ///
+    /// # Errors
+    /// Returns every bridge's status or read error, and the result of disabling
+    /// them, if any bridge tripped.
+    pub async fn supervise(&mut self) -> Result<[Status; N], Tripped<ESPI, N>> {
+        let statuses = self.faults().await;
+
+        let tripped = statuses
+            .iter()
+            .any(|status| status.as_ref().map_or(true, Status::is_critical));
+
+        if tripped {
+            let disabled = self.disable_all().await;
+            return Err(Tripped { statuses, disabled });
+        }
+
+        // None of them failed to read.
+        Ok(statuses.map(|status| status.unwrap_or_else(|_| unreachable!())))
+    }
+
+    /// Clears the provided flags in the status register of every bridge.
+    ///
+    /// # Errors
+    /// Propagates errors from any bridge.
+    pub async fn clear_faults(&mut self, c: Status) -> Result<(), Error<ESPI>> {
+        for (i, driver) in self.drivers.iter_mut().enumerate() {
+            driver.clear_status(c).await.map_err(|e| Error::Spi(i, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Access a single bridge. Changes made through it are not tracked by the
+    /// manager.
+    pub fn driver(&mut self, index: usize) -> Option<&mut Driver<SPI, EN, DIS, DEL>> {
+        self.drivers.get_mut(index)
+    }
+
+    /// Release the bridges.
+    pub fn release(self) -> [Driver<SPI, EN, DIS, DEL>; N] {
+        self.drivers
+    }
+}
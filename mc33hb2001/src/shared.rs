@@ -0,0 +1,133 @@
+//! Sharing a single driver between concurrent async tasks.
+//!
+//! Every register access holds the lock for its whole SPI exchange, so the two
+//! frames of a read can never be interleaved with another task's access.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+
+use crate::{Configuration, Configure, Driver, Status, StatusMask};
+
+/// MC33HB2001 driver guarded by an async mutex.
+pub struct SharedDriver<M: RawMutex, SPI, EN, DIS, DEL> {
+    driver: Mutex<M, Driver<SPI, EN, DIS, DEL>>,
+}
+
+impl<M: RawMutex, SPI, EN, DIS, DEL> SharedDriver<M, SPI, EN, DIS, DEL> {
+    /// Wraps a driver so it can be shared between tasks.
+    pub const fn new(driver: Driver<SPI, EN, DIS, DEL>) -> Self {
+        Self {
+            driver: Mutex::new(driver),
+        }
+    }
+
+    /// Creates a cloneable handle to the driver.
+    pub fn handle(&self) -> Handle<'_, M, SPI, EN, DIS, DEL> {
+        Handle { shared: self }
+    }
+
+    /// Release the wrapped driver.
+    pub fn release(self) -> Driver<SPI, EN, DIS, DEL> {
+        self.driver.into_inner()
+    }
+}
+
+/// Cloneable handle to a [`SharedDriver`].
+pub struct Handle<'a, M: RawMutex, SPI, EN, DIS, DEL> {
+    shared: &'a SharedDriver<M, SPI, EN, DIS, DEL>,
+}
+
+impl<M: RawMutex, SPI, EN, DIS, DEL> Clone for Handle<'_, M, SPI, EN, DIS, DEL> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: RawMutex, SPI, EN, DIS, DEL> Copy for Handle<'_, M, SPI, EN, DIS, DEL> {}
+
+impl<'a, M, SPI, EN, DIS, DEL, ESPI, EEN, EDIS> Handle<'a, M, SPI, EN, DIS, DEL>
+where
+    M: RawMutex,
+    SPI: embedded_hal_async::spi::SpiDevice<Error = ESPI>,
+    EN: embedded_hal::digital::OutputPin<Error = EEN>,
+    DIS: embedded_hal::digital::OutputPin<Error = EDIS>,
+    DEL: embedded_hal_async::delay::DelayNs,
+{
+    /// Wait for exclusive access to the driver. Other handles are blocked until
+    /// the guard is dropped, so several accesses can be made atomically.
+    pub async fn lock(&self) -> MutexGuard<'a, M, Driver<SPI, EN, DIS, DEL>> {
+        self.shared.driver.lock().await
+    }
+
+    /// Get the configuration and control register content.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn configuration(&self) -> Result<Configuration, ESPI> {
+        self.lock().await.configuration().await
+    }
+
+    /// Set the configuration and control register content.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn set_configuration(&self, c: Configuration) -> Result<(), ESPI> {
+        self.lock().await.set_configuration(c).await
+    }
+
+    /// Set the configuration and control register content, refusing to send
+    /// configurations that fail [`Configuration::validate`].
+    ///
+    /// # Errors
+    /// Returns an error if the configuration is invalid, and propagates errors
+    /// from the SPI bus.
+    pub async fn set_configuration_checked(&self, c: Configuration) -> Result<(), Configure<ESPI>> {
+        self.lock().await.set_configuration_checked(c).await
+    }
+
+    /// Read, modify and write back the configuration and control register
+    /// content without another handle accessing the driver in between.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn modify_configuration(
+        &self,
+        f: impl FnOnce(Configuration) -> Configuration,
+    ) -> Result<(), ESPI> {
+        let mut driver = self.lock().await;
+        let c = driver.configuration().await?;
+        driver.set_configuration(f(c)).await
+    }
+
+    /// Get the status register content.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn status(&self) -> Result<Status, ESPI> {
+        self.lock().await.status().await
+    }
+
+    /// Clears the provided flags in the status register.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn clear_status(&self, c: Status) -> Result<(), ESPI> {
+        self.lock().await.clear_status(c).await
+    }
+
+    /// Get the status mask register content.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn status_mask(&self) -> Result<StatusMask, ESPI> {
+        self.lock().await.status_mask().await
+    }
+
+    /// Set the status mask register content
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus.
+    pub async fn set_status_mask(&self, c: StatusMask) -> Result<(), ESPI> {
+        self.lock().await.set_status_mask(c).await
+    }
+}
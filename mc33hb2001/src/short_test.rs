@@ -0,0 +1,116 @@
+use crate::{BridgeMode, Configuration, ControlMode, Driver, Input, Register, Status};
+
+/// Time each output is driven for before its status is read. Units of
+/// microseconds.
+const DRIVE_DURATION_US: u32 = 100;
+
+/// Short-circuits detected on a single output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+pub struct OutputShorts {
+    /// Output is shorted to ground, detected while driven high.
+    pub to_ground: bool,
+    /// Output is shorted to VPWR, detected while driven low.
+    pub to_power: bool,
+}
+
+impl OutputShorts {
+    /// Returns true if any short-circuit was detected.
+    #[must_use]
+    pub const fn is_shorted(&self) -> bool {
+        self.to_ground || self.to_power
+    }
+}
+
+/// Result of the output short-circuit test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, defmt::Format)]
+pub struct ShortTest {
+    /// Verdict for OUT1.
+    pub output_1: OutputShorts,
+    /// Verdict for OUT2.
+    pub output_2: OutputShorts,
+}
+
+impl ShortTest {
+    /// Returns true if neither output is shorted.
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        !self.output_1.is_shorted() && !self.output_2.is_shorted()
+    }
+}
+
+impl<SPI, EN, DIS, DEL, ESPI, EEN, EDIS> Driver<SPI, EN, DIS, DEL>
+where
+    SPI: embedded_hal_async::spi::SpiDevice<Error = ESPI>,
+    EN: embedded_hal::digital::OutputPin<Error = EEN>,
+    DIS: embedded_hal::digital::OutputPin<Error = EDIS>,
+    DEL: embedded_hal_async::delay::DelayNs,
+{
+    /// Check OUT1 and OUT2 for short-circuits to ground or VPWR. Both outputs
+    /// are first driven low together, then each is driven high on its own, in
+    /// [`BridgeMode::HalfBridge`]. The load sees VPWR briefly while an output
+    /// is high. The current limit, slew rate and other settings of the current
+    /// configuration are kept while driving.
+    ///
+    /// The configuration is restored and the status register cleared
+    /// afterwards.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus. The output is disabled, on a best
+    /// effort basis, before returning an error.
+    pub async fn short_test(&mut self) -> Result<ShortTest, ESPI> {
+        let previous = self.configuration().await?;
+
+        match self.drive_outputs(previous).await {
+            Ok(result) => {
+                self.set_configuration(previous).await?;
+                self.clear_all_status().await?;
+                Ok(result)
+            }
+            Err(e) => {
+                // The bus has just failed, so this may fail the same way.
+                let _ = self.set_configuration(previous.with_enable(false)).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn drive_outputs(&mut self, previous: Configuration) -> Result<ShortTest, ESPI> {
+        let drive = |in1, in2| {
+            previous
+                .with_enable(true)
+                .with_bridge_mode(BridgeMode::HalfBridge)
+                .with_control_mode(ControlMode::Spi)
+                .with_virtual_input_1(in1)
+                .with_virtual_input_2(in2)
+        };
+
+        let low = self.drive_and_read(drive(Input::Low, Input::Low)).await?;
+        let high_1 = self.drive_and_read(drive(Input::High, Input::Low)).await?;
+        let high_2 = self.drive_and_read(drive(Input::Low, Input::High)).await?;
+
+        Ok(ShortTest {
+            output_1: OutputShorts {
+                to_ground: high_1.sc_ground_output_1(),
+                to_power: low.sc_power_output_1(),
+            },
+            output_2: OutputShorts {
+                to_ground: high_2.sc_ground_output_2(),
+                to_power: low.sc_power_output_2(),
+            },
+        })
+    }
+
+    async fn drive_and_read(&mut self, c: Configuration) -> Result<Status, ESPI> {
+        // A latched short-circuit disables the outputs until cleared.
+        self.clear_all_status().await?;
+        self.set_configuration(c).await?;
+        self.delay.delay_us(DRIVE_DURATION_US).await;
+        let status = self.status().await?;
+        self.set_configuration(c.with_enable(false)).await?;
+        Ok(status)
+    }
+
+    async fn clear_all_status(&mut self) -> Result<(), ESPI> {
+        self.write(Register::Status, u16::MAX).await
+    }
+}
@@ -0,0 +1,321 @@
+//! Software PWM on an SPI virtual input, for boards where IN1 and IN2 are not
+//! wired.
+//!
+//! [`SoftPwm::tick`] is called from a periodic hardware-timer interrupt and
+//! sends a pre-encoded frame over a blocking SPI device on each edge, so the
+//! output timing does not depend on executor load. The duty resolution is the
+//! number of timer ticks in one PWM period.
+//!
+//! [`SoftPwm::disable`] sends the base configuration with the outputs disabled
+//! and stops any further frames, for shutting down from outside the interrupt.
+
+use crate::{Configuration, ControlMode, Input, Register};
+
+/// Virtual input driven by the PWM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Channel {
+    /// Drive virtual input 1.
+    VirtualInput1,
+    /// Drive virtual input 2.
+    VirtualInput2,
+}
+
+/// Achievable output for a requested frequency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Resolution {
+    /// Number of duty steps in one period.
+    pub steps: u32,
+    /// Actual output frequency. Units of Hertz.
+    pub frequency_hz: u32,
+}
+
+/// Timer-driven software PWM engine.
+pub struct SoftPwm {
+    timer_hz: u32,
+    frames: [[u8; 2]; 2],
+    disabled_frame: [u8; 2],
+    disabled: bool,
+    period: u32,
+    on: u32,
+    pending: Option<(u32, u32)>,
+    tick: u32,
+    output: Option<bool>,
+}
+
+impl SoftPwm {
+    /// Creates a new engine driving `channel`, with every other setting taken
+    /// from `base`. `timer_hz` is the rate [`SoftPwm::tick`] is called at. The
+    /// output stays low until [`SoftPwm::set`] is called.
+    #[must_use]
+    pub const fn new(base: Configuration, channel: Channel, timer_hz: u32) -> Self {
+        let base = base.with_control_mode(ControlMode::Spi);
+
+        let (low, high) = match channel {
+            Channel::VirtualInput1 => (
+                base.with_virtual_input_1(Input::Low),
+                base.with_virtual_input_1(Input::High),
+            ),
+            Channel::VirtualInput2 => (
+                base.with_virtual_input_2(Input::Low),
+                base.with_virtual_input_2(Input::High),
+            ),
+        };
+
+        Self {
+            timer_hz,
+            frames: [
+                crate::write_frame(Register::ConfigAndControl, low.into_bits()),
+                crate::write_frame(Register::ConfigAndControl, high.into_bits()),
+            ],
+            disabled_frame: crate::write_frame(
+                Register::ConfigAndControl,
+                base.with_enable(false).into_bits(),
+            ),
+            disabled: false,
+            period: 1,
+            on: 0,
+            pending: None,
+            tick: 0,
+            output: None,
+        }
+    }
+
+    /// Set the target frequency and duty, as a fraction of [`u16::MAX`]. The
+    /// change takes effect at the start of the next period.
+    ///
+    /// Returns the achievable resolution, or `None` if the frequency is zero or
+    /// above half the timer rate.
+    pub fn set(&mut self, frequency_hz: u32, duty: u16) -> Option<Resolution> {
+        if frequency_hz == 0 {
+            return None;
+        }
+
+        let period = (self.timer_hz + frequency_hz / 2) / frequency_hz;
+        if period < 2 {
+            return None;
+        }
+
+        let on =
+            (u64::from(period) * u64::from(duty) + u64::from(u16::MAX / 2)) / u64::from(u16::MAX);
+        #[allow(clippy::cast_possible_truncation)]
+        let on = on as u32; // Never more than `period`.
+
+        self.pending = Some((period, on));
+
+        Some(self.resolution_of(period))
+    }
+
+    /// Resolution of the output currently being generated.
+    #[must_use]
+    pub fn resolution(&self) -> Resolution {
+        self.resolution_of(self.period)
+    }
+
+    /// Advance by one timer tick, sending a frame if the output changes. Must
+    /// be called at the rate given to [`SoftPwm::new`].
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus. The frame is retried on the next
+    /// tick.
+    pub fn tick<SPI>(&mut self, spi: &mut SPI) -> Result<(), SPI::Error>
+    where
+        SPI: embedded_hal::spi::SpiDevice,
+    {
+        if self.disabled {
+            return Ok(());
+        }
+
+        if self.tick == 0 {
+            if let Some((period, on)) = self.pending.take() {
+                self.period = period;
+                self.on = on;
+            }
+        }
+
+        let high = self.tick < self.on;
+
+        self.tick += 1;
+        if self.tick >= self.period {
+            self.tick = 0;
+        }
+
+        if self.output != Some(high) {
+            self.output = None;
+            spi.write(&self.frames[usize::from(high)])?;
+            self.output = Some(high);
+        }
+
+        Ok(())
+    }
+
+    /// Disable the outputs, and stop [`SoftPwm::tick`] sending any more
+    /// frames.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus. Ticks stay stopped, so the call can
+    /// be retried.
+    pub fn disable<SPI>(&mut self, spi: &mut SPI) -> Result<(), SPI::Error>
+    where
+        SPI: embedded_hal::spi::SpiDevice,
+    {
+        self.disabled = true;
+        self.output = None;
+        spi.write(&self.disabled_frame)
+    }
+
+    fn resolution_of(&self, period: u32) -> Resolution {
+        Resolution {
+            steps: period,
+            frequency_hz: self.timer_hz / period,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use embedded_hal::spi::Operation;
+
+    use super::*;
+
+    /// Records written frames.
+    #[derive(Default)]
+    struct MockSpi {
+        written: Vec<[u8; 2]>,
+    }
+
+    impl embedded_hal::spi::ErrorType for MockSpi {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal::spi::SpiDevice for MockSpi {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+            for operation in operations {
+                let Operation::Write(words) = operation else { unimplemented!() };
+                self.written.push([words[0], words[1]]);
+            }
+            Ok(())
+        }
+    }
+
+    const TIMER_HZ: u32 = 1_000;
+
+    fn frame(c: Configuration) -> [u8; 2] {
+        crate::write_frame(
+            Register::ConfigAndControl,
+            c.with_control_mode(ControlMode::Spi).into_bits(),
+        )
+    }
+
+    fn level(input: Input) -> [u8; 2] {
+        frame(Configuration::new().with_virtual_input_2(input))
+    }
+
+    /// Frame sent by each of `ticks` ticks, if any.
+    fn run(pwm: &mut SoftPwm, ticks: usize) -> Vec<Option<[u8; 2]>> {
+        let mut spi = MockSpi::default();
+        (0..ticks)
+            .map(|_| {
+                let Ok(()) = pwm.tick(&mut spi);
+                spi.written.pop()
+            })
+            .collect()
+    }
+
+    fn pwm(frequency_hz: u32, duty: u16) -> SoftPwm {
+        let mut pwm = SoftPwm::new(Configuration::new(), Channel::VirtualInput2, TIMER_HZ);
+        pwm.set(frequency_hz, duty).unwrap();
+        pwm
+    }
+
+    #[test]
+    fn edges_over_one_period() {
+        let mut pwm = pwm(100, u16::MAX / 10 * 3);
+
+        let mut expected = std::vec![None; 11];
+        expected[0] = Some(level(Input::High));
+        expected[3] = Some(level(Input::Low));
+        expected[10] = Some(level(Input::High));
+
+        assert_eq!(run(&mut pwm, 11), expected);
+    }
+
+    #[test]
+    fn zero_duty_stays_low() {
+        let mut pwm = pwm(100, 0);
+
+        let mut expected = std::vec![None; 30];
+        expected[0] = Some(level(Input::Low));
+
+        assert_eq!(run(&mut pwm, 30), expected);
+    }
+
+    #[test]
+    fn full_duty_stays_high() {
+        let mut pwm = pwm(100, u16::MAX);
+
+        let mut expected = std::vec![None; 30];
+        expected[0] = Some(level(Input::High));
+
+        assert_eq!(run(&mut pwm, 30), expected);
+    }
+
+    #[test]
+    fn change_waits_for_next_period() {
+        let mut pwm = pwm(100, u16::MAX);
+        run(&mut pwm, 5);
+
+        pwm.set(100, 0).unwrap();
+
+        let mut expected = std::vec![None; 6];
+        expected[5] = Some(level(Input::Low));
+
+        assert_eq!(run(&mut pwm, 6), expected);
+    }
+
+    #[test]
+    fn resolution() {
+        let mut pwm = SoftPwm::new(Configuration::new(), Channel::VirtualInput1, TIMER_HZ);
+
+        assert_eq!(
+            pwm.set(100, 0),
+            Some(Resolution {
+                steps: 10,
+                frequency_hz: 100
+            })
+        );
+        assert_eq!(
+            pwm.set(300, 0),
+            Some(Resolution {
+                steps: 3,
+                frequency_hz: 333
+            })
+        );
+    }
+
+    #[test]
+    fn unreachable_frequencies() {
+        let mut pwm = SoftPwm::new(Configuration::new(), Channel::VirtualInput1, TIMER_HZ);
+
+        assert_eq!(pwm.set(0, u16::MAX / 2), None);
+        assert_eq!(pwm.set(TIMER_HZ / 2 + TIMER_HZ / 5, u16::MAX / 2), None);
+        assert_eq!(pwm.set(u32::MAX, u16::MAX / 2), None);
+    }
+
+    #[test]
+    fn disable_stops_ticks() {
+        let mut pwm = pwm(100, u16::MAX / 2);
+        let mut spi = MockSpi::default();
+
+        let Ok(()) = pwm.disable(&mut spi);
+        assert_eq!(
+            spi.written,
+            [frame(Configuration::new().with_enable(false))]
+        );
+
+        assert_eq!(run(&mut pwm, 20), std::vec![None; 20]);
+    }
+}
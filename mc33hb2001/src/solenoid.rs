@@ -0,0 +1,174 @@
+//! Peak-and-hold driving of solenoid and injector loads.
+//!
+//! The load is pulled in at full duty with a high current limit, then held at
+//! a reduced duty and current limit. The output duty is produced by a PWM
+//! channel driving IN1, so the bridge is used in [`ControlMode::Parallel`].
+
+use crate::{Configuration, ControlMode, CurrentLimit, Driver, Status};
+
+/// Possible solenoid errors.
+#[derive(Debug, defmt::Format)]
+pub enum Error<ESPI, EPWM> {
+    /// Error with the SPI bus.
+    Spi(ESPI),
+    /// Error setting the output duty.
+    Pwm(EPWM),
+    /// The load did not pull in. The status shows whether current limiting or
+    /// an open load was detected. The output has been switched off.
+    PullIn(Status),
+}
+
+/// Peak-and-hold timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct PeakHold {
+    /// Current limit while pulling in.
+    pub peak_limit: CurrentLimit,
+    /// Time spent at full duty while pulling in. Units of microseconds.
+    pub peak_duration_us: u32,
+    /// Current limit while holding.
+    pub hold_limit: CurrentLimit,
+    /// Output duty while holding, as a fraction of [`u16::MAX`].
+    pub hold_duty: u16,
+}
+
+impl Default for PeakHold {
+    fn default() -> Self {
+        Self {
+            peak_limit: CurrentLimit::Lim10_7,
+            peak_duration_us: 5_000,
+            hold_limit: CurrentLimit::Lim5_4,
+            hold_duty: u16::MAX / 4,
+        }
+    }
+}
+
+/// Solenoid or injector load driven with a peak-and-hold timeline.
+pub struct Solenoid<SPI, EN, DIS, DEL, PWM, TIM> {
+    driver: Driver<SPI, EN, DIS, DEL>,
+    pwm: PWM,
+    timer: TIM,
+    base: Configuration,
+    timeline: PeakHold,
+}
+
+impl<SPI, EN, DIS, DEL, PWM, TIM, ESPI, EEN, EDIS, EPWM> Solenoid<SPI, EN, DIS, DEL, PWM, TIM>
+where
+    SPI: embedded_hal_async::spi::SpiDevice<Error = ESPI>,
+    EN: embedded_hal::digital::OutputPin<Error = EEN>,
+    DIS: embedded_hal::digital::OutputPin<Error = EDIS>,
+    DEL: embedded_hal_async::delay::DelayNs,
+    PWM: embedded_hal::pwm::SetDutyCycle<Error = EPWM>,
+    TIM: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new solenoid from a bridge, a PWM channel driving its IN1
+    /// pin, and a delay implementation used to time the peak. The output is
+    /// left off.
+    ///
+    /// The control mode, current limit and enable of `base` are overridden,
+    /// everything else (bridge mode, slew rate, etc.) is applied to the bridge.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus and PWM channel.
+    pub async fn new(
+        driver: Driver<SPI, EN, DIS, DEL>,
+        pwm: PWM,
+        timer: TIM,
+        base: Configuration,
+        timeline: PeakHold,
+    ) -> Result<Self, Error<ESPI, EPWM>> {
+        let mut this = Self {
+            driver,
+            pwm,
+            timer,
+            base: base.with_control_mode(ControlMode::Parallel),
+            timeline,
+        };
+
+        this.de_energise().await?;
+
+        Ok(this)
+    }
+
+    /// Current peak-and-hold timeline.
+    pub fn timeline(&self) -> PeakHold {
+        self.timeline
+    }
+
+    /// Set the peak-and-hold timeline used by the next
+    /// [`Solenoid::energise`].
+    pub fn set_timeline(&mut self, timeline: PeakHold) {
+        self.timeline = timeline;
+    }
+
+    /// Pull the load in, then switch to holding it. Returns once the hold
+    /// level is applied.
+    ///
+    /// # Errors
+    /// Returns an error if current limiting or an open load was detected while
+    /// pulling in, and propagates errors from the SPI bus and PWM channel.
+    pub async fn energise(&mut self) -> Result<(), Error<ESPI, EPWM>> {
+        let timeline = self.timeline;
+
+        // Start from a clean slate so only faults from this pull-in are seen.
+        self.driver
+            .clear_status(Status::from_bits(u16::MAX))
+            .await
+            .map_err(Error::Spi)?;
+
+        self.driver
+            .set_configuration(
+                self.base
+                    .with_current_limit(timeline.peak_limit)
+                    .with_enable(true),
+            )
+            .await
+            .map_err(Error::Spi)?;
+        self.pwm.set_duty_cycle_fully_on().map_err(Error::Pwm)?;
+
+        self.timer.delay_us(timeline.peak_duration_us).await;
+
+        let status = self.driver.status().await.map_err(Error::Spi)?;
+        if status.overcurrent() || status.open_load() {
+            // The status read just succeeded, so the bus is working, and the
+            // status explains the failed pull-in.
+            let _ = self.de_energise().await;
+            return Err(Error::PullIn(status));
+        }
+
+        self.driver
+            .set_configuration(
+                self.base
+                    .with_current_limit(timeline.hold_limit)
+                    .with_enable(true),
+            )
+            .await
+            .map_err(Error::Spi)?;
+        self.pwm
+            .set_duty_cycle_fraction(timeline.hold_duty, u16::MAX)
+            .map_err(Error::Pwm)?;
+
+        Ok(())
+    }
+
+    /// Switch the output off and disable the bridge at the hold current
+    /// limit.
+    ///
+    /// # Errors
+    /// Propagates errors from the SPI bus and PWM channel.
+    pub async fn de_energise(&mut self) -> Result<(), Error<ESPI, EPWM>> {
+        self.pwm.set_duty_cycle_fully_off().map_err(Error::Pwm)?;
+        self.driver
+            .set_configuration(
+                self.base
+                    .with_current_limit(self.timeline.hold_limit)
+                    .with_enable(false),
+            )
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Release the bridge, PWM channel and delay implementation.
+    pub fn release(self) -> (Driver<SPI, EN, DIS, DEL>, PWM, TIM) {
+        (self.driver, self.pwm, self.timer)
+    }
+}
@@ -0,0 +1,399 @@
+//! Bipolar stepper motor control using two MC33HB2001 bridges, one per coil.
+//!
+//! Positions are tracked in half-steps, the finest resolution the sequences
+//! can produce, so changing [`StepMode`] never loses the position.
+
+use crate::{BridgeMode, Configuration, ControlMode, Driver, Input, Status};
+
+/// Half-step sequence of coil currents. Even entries energise a single coil,
+/// odd entries energise both.
+const SEQUENCE: [(Current, Current); 8] = [
+    (Current::Positive, Current::Off),
+    (Current::Positive, Current::Positive),
+    (Current::Off, Current::Positive),
+    (Current::Negative, Current::Positive),
+    (Current::Negative, Current::Off),
+    (Current::Negative, Current::Negative),
+    (Current::Off, Current::Negative),
+    (Current::Positive, Current::Negative),
+];
+
+/// One of the two stepper coils.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Coil {
+    /// Coil driven by the first bridge.
+    A,
+    /// Coil driven by the second bridge.
+    B,
+}
+
+/// Possible stepper errors.
+#[derive(Debug, defmt::Format)]
+pub enum Error<ESPI> {
+    /// Error with the SPI bus of a coil's bridge.
+    Spi(Coil, ESPI),
+    /// A coil's bridge reported a fault. Both coils have been de-energised.
+    Fault(Coil, Status),
+}
+
+/// Stepping sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum StepMode {
+    /// Both coils energised at all times. Each step moves two half-steps.
+    FullStep,
+    /// Alternates between one and both coils energised. Each step moves one
+    /// half-step.
+    HalfStep,
+    /// One coil energised at a time. Each step moves two half-steps.
+    Wave,
+}
+
+impl StepMode {
+    const fn half_steps(self) -> u8 {
+        match self {
+            StepMode::FullStep | StepMode::Wave => 2,
+            StepMode::HalfStep => 1,
+        }
+    }
+
+    /// Returns true if the sequence uses the given half-step phase.
+    const fn uses_phase(self, phase: u8) -> bool {
+        match self {
+            StepMode::FullStep => phase & 1 == 1,
+            StepMode::Wave => phase & 1 == 0,
+            StepMode::HalfStep => true,
+        }
+    }
+}
+
+/// Direction of rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Direction {
+    /// Increasing position.
+    Forward,
+    /// Decreasing position.
+    Reverse,
+}
+
+/// Trapezoidal step-rate profile used for moves. Units of steps per second and
+/// steps per second squared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Ramp {
+    /// Step rate at the start and end of a move.
+    pub start_rate: u32,
+    /// Highest step rate reached during a move.
+    pub max_rate: u32,
+    /// Rate of change of the step rate. Zero runs the whole move at
+    /// `max_rate`.
+    pub acceleration: u32,
+}
+
+impl Default for Ramp {
+    fn default() -> Self {
+        Self {
+            start_rate: 100,
+            max_rate: 500,
+            acceleration: 1000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Current {
+    Positive,
+    Negative,
+    Off,
+}
+
+/// Bipolar stepper motor driven by two MC33HB2001 bridges.
+pub struct Stepper<SPI, EN, DIS, DEL, TIM> {
+    coil_a: Driver<SPI, EN, DIS, DEL>,
+    coil_b: Driver<SPI, EN, DIS, DEL>,
+    timer: TIM,
+    base: Configuration,
+    mode: StepMode,
+    ramp: Ramp,
+    phase: u8,
+    position: i32,
+    currents: (Current, Current),
+}
+
+impl<SPI, EN, DIS, DEL, TIM, ESPI, EEN, EDIS> Stepper<SPI, EN, DIS, DEL, TIM>
+where
+    SPI: embedded_hal_async::spi::SpiDevice<Error = ESPI>,
+    EN: embedded_hal::digital::OutputPin<Error = EEN>,
+    DIS: embedded_hal::digital::OutputPin<Error = EDIS>,
+    DEL: embedded_hal_async::delay::DelayNs,
+    TIM: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new stepper from the bridges driving coils A and B, and a
+    /// delay implementation used to time steps. The coils are energised in the
+    /// first phase of the sequence, which becomes position zero.
+    ///
+    /// The control and bridge modes of `base` are overridden, everything else
+    /// (current limit, slew rate, etc.) is applied to both bridges.
+    ///
+    /// # Errors
+    /// Propagates errors from either coil.
+    pub async fn new(
+        coil_a: Driver<SPI, EN, DIS, DEL>,
+        coil_b: Driver<SPI, EN, DIS, DEL>,
+        timer: TIM,
+        base: Configuration,
+        mode: StepMode,
+    ) -> Result<Self, Error<ESPI>> {
+        let mut this = Self {
+            coil_a,
+            coil_b,
+            timer,
+            base: base
+                .with_control_mode(ControlMode::Spi)
+                .with_bridge_mode(BridgeMode::HBridge),
+            mode,
+            ramp: Ramp::default(),
+            phase: u8::from(!mode.uses_phase(0)),
+            position: 0,
+            currents: (Current::Off, Current::Off),
+        };
+
+        this.apply(SEQUENCE[usize::from(this.phase)], true).await?;
+
+        Ok(this)
+    }
+
+    /// Current position in half-steps.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Redefine the current position, for example after homing.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Current stepping sequence.
+    pub fn step_mode(&self) -> StepMode {
+        self.mode
+    }
+
+    /// Change the stepping sequence. If the current phase is not part of the
+    /// new sequence, the next step moves a single half-step to rejoin it.
+    pub fn set_step_mode(&mut self, mode: StepMode) {
+        self.mode = mode;
+    }
+
+    /// Current step-rate profile.
+    pub fn ramp(&self) -> Ramp {
+        self.ramp
+    }
+
+    /// Set the step-rate profile used by [`Stepper::move_by`] and
+    /// [`Stepper::move_to`].
+    pub fn set_ramp(&mut self, ramp: Ramp) {
+        self.ramp = ramp;
+    }
+
+    /// Perform a single step immediately, then check both coils for faults.
+    ///
+    /// # Errors
+    /// Propagates errors from either coil. On a fault both coils are
+    /// de-energised.
+    pub async fn step(&mut self, direction: Direction) -> Result<(), Error<ESPI>> {
+        let half_steps = if self.mode.uses_phase(self.phase) {
+            self.mode.half_steps()
+        } else {
+            1
+        };
+
+        let phase = match direction {
+            Direction::Forward => (self.phase + half_steps) % 8,
+            Direction::Reverse => (self.phase + 8 - half_steps) % 8,
+        };
+        let position = match direction {
+            Direction::Forward => self.position.wrapping_add(i32::from(half_steps)),
+            Direction::Reverse => self.position.wrapping_sub(i32::from(half_steps)),
+        };
+
+        // Only track the step once the coils have actually moved to it.
+        self.apply(SEQUENCE[usize::from(phase)], false).await?;
+        self.phase = phase;
+        self.position = position;
+
+        self.check_faults().await
+    }
+
+    /// Move by a number of steps of the current [`StepMode`], following the
+    /// configured [`Ramp`]. Negative values move in reverse.
+    ///
+    /// # Errors
+    /// Propagates errors from either coil. On a fault both coils are
+    /// de-energised and the move stops.
+    pub async fn move_by(&mut self, steps: i32) -> Result<(), Error<ESPI>> {
+        let direction = if steps < 0 {
+            Direction::Reverse
+        } else {
+            Direction::Forward
+        };
+
+        let ramp = self.ramp;
+        let start_rate = u64::from(ramp.start_rate.max(1));
+        let max_rate = u64::from(ramp.max_rate).max(start_rate);
+        let acceleration = u64::from(ramp.acceleration);
+
+        let mut rate = if acceleration == 0 {
+            max_rate
+        } else {
+            start_rate
+        };
+
+        let mut remaining = steps.unsigned_abs();
+        while remaining > 0 {
+            self.step(direction).await?;
+            remaining -= 1;
+
+            if remaining == 0 {
+                break;
+            }
+
+            let period_us = 1_000_000 / rate;
+            self.timer
+                .delay_us(u32::try_from(period_us).unwrap_or(u32::MAX))
+                .await;
+
+            if acceleration != 0 {
+                let change = (acceleration * period_us / 1_000_000).max(1);
+                let stopping_steps = (rate * rate - start_rate * start_rate) / (2 * acceleration);
+
+                rate = if u64::from(remaining) <= stopping_steps {
+                    rate.saturating_sub(change).max(start_rate)
+                } else {
+                    (rate + change).min(max_rate)
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move to an absolute position in half-steps, following the configured
+    /// [`Ramp`]. With a sequence moving two half-steps per step, an odd
+    /// distance stops one half-step short. Distances too large for an `i32`
+    /// saturate.
+    ///
+    /// # Errors
+    /// Propagates errors from either coil. On a fault both coils are
+    /// de-energised and the move stops.
+    pub async fn move_to(&mut self, position: i32) -> Result<(), Error<ESPI>> {
+        let mut distance = position.saturating_sub(self.position);
+
+        // Rejoin the sequence first, so the remaining distance divides evenly.
+        if distance != 0 && !self.mode.uses_phase(self.phase) {
+            let direction = if distance < 0 {
+                Direction::Reverse
+            } else {
+                Direction::Forward
+            };
+            self.step(direction).await?;
+            distance = position.saturating_sub(self.position);
+        }
+
+        self.move_by(distance / i32::from(self.mode.half_steps()))
+            .await
+    }
+
+    /// De-energise both coils, removing holding torque. The next step
+    /// re-energises them.
+    ///
+    /// # Errors
+    /// Propagates errors from either coil.
+    pub async fn de_energise(&mut self) -> Result<(), Error<ESPI>> {
+        self.apply((Current::Off, Current::Off), false).await
+    }
+
+    /// Read the status of both coils' bridges, de-energising both coils if
+    /// either has a critical fault, see [`Status::is_critical`].
+    ///
+    /// # Errors
+    /// Returns the first coil found with a critical fault, and propagates
+    /// errors from either coil.
+    pub async fn check_faults(&mut self) -> Result<(), Error<ESPI>> {
+        for coil in [Coil::A, Coil::B] {
+            let status = self
+                .driver(coil)
+                .status()
+                .await
+                .map_err(|e| Error::Spi(coil, e))?;
+
+            if status.is_critical() {
+                // A bridge in fault may reject the write, and the status says
+                // why the motor stopped.
+                let _ = self.de_energise().await;
+                return Err(Error::Fault(coil, status));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clear all status flags of a coil's bridge after a fault.
+    ///
+    /// # Errors
+    /// Propagates errors from the coil.
+    pub async fn clear_faults(&mut self, coil: Coil) -> Result<(), Error<ESPI>> {
+        self.driver(coil)
+            .clear_status(Status::from_bits(u16::MAX))
+            .await
+            .map_err(|e| Error::Spi(coil, e))
+    }
+
+    /// Release the bridges and delay implementation.
+    #[allow(clippy::type_complexity)]
+    pub fn release(self) -> (Driver<SPI, EN, DIS, DEL>, Driver<SPI, EN, DIS, DEL>, TIM) {
+        (self.coil_a, self.coil_b, self.timer)
+    }
+
+    fn driver(&mut self, coil: Coil) -> &mut Driver<SPI, EN, DIS, DEL> {
+        match coil {
+            Coil::A => &mut self.coil_a,
+            Coil::B => &mut self.coil_b,
+        }
+    }
+
+    async fn apply(
+        &mut self,
+        currents: (Current, Current),
+        force: bool,
+    ) -> Result<(), Error<ESPI>> {
+        for (coil, current, previous) in [
+            (Coil::A, currents.0, self.currents.0),
+            (Coil::B, currents.1, self.currents.1),
+        ] {
+            if force || current != previous {
+                let (in1, in2) = match current {
+                    Current::Positive => (Input::High, Input::Low),
+                    Current::Negative => (Input::Low, Input::High),
+                    Current::Off => (Input::Low, Input::Low),
+                };
+
+                let configuration = self
+                    .base
+                    .with_virtual_input_1(in1)
+                    .with_virtual_input_2(in2);
+
+                self.driver(coil)
+                    .set_configuration(configuration)
+                    .await
+                    .map_err(|e| Error::Spi(coil, e))?;
+
+                // Cached per coil, so a failure on coil B doesn't lose coil
+                // A's write.
+                match coil {
+                    Coil::A => self.currents.0 = current,
+                    Coil::B => self.currents.1 = current,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
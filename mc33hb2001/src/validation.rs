@@ -0,0 +1,122 @@
+use crate::{BridgeMode, Configuration, ControlMode, Input};
+
+/// Set of rules broken by a [`Configuration`].
+#[bitfield_struct::bitfield(u8, defmt = true)]
+#[derive(PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct Violations {
+    /// A virtual input is set high while in [`ControlMode::Parallel`], where
+    /// it is ignored.
+    pub virtual_input_in_parallel_mode: bool,
+
+    /// An open load check is requested while outputs are enabled. Open load
+    /// checks only run in Standby.
+    pub open_load_check_outside_standby: bool,
+
+    /// An open load check is requested in [`BridgeMode::HalfBridge`]. Open load
+    /// checks only run in Full Bridge Standby.
+    pub open_load_check_in_half_bridge: bool,
+
+    /// Thermal management is enabled without active current limiting, so it has
+    /// no effect.
+    pub thermal_management_without_current_limit: bool,
+
+    #[bits(4)]
+    __: u8,
+}
+
+impl Violations {
+    /// Returns true if no rules were broken.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.into_bits() == 0
+    }
+}
+
+impl Configuration {
+    /// Checks the configuration for combinations of settings that make no
+    /// sense to the chip.
+    #[must_use]
+    pub const fn validate(&self) -> Violations {
+        let virtual_input_in_parallel_mode = matches!(self.control_mode(), ControlMode::Parallel)
+            && (matches!(self.virtual_input_1(), Input::High)
+                || matches!(self.virtual_input_2(), Input::High));
+
+        Violations::new()
+            .with_virtual_input_in_parallel_mode(virtual_input_in_parallel_mode)
+            .with_open_load_check_outside_standby(self.check_for_open_load() && self.enable())
+            .with_open_load_check_in_half_bridge(
+                self.check_for_open_load() && matches!(self.bridge_mode(), BridgeMode::HalfBridge),
+            )
+            .with_thermal_management_without_current_limit(
+                self.enable_thermal_management() && !self.enable_active_current_limit(),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_valid() {
+        assert!(Configuration::new().validate().is_empty());
+    }
+
+    #[test]
+    fn virtual_input_in_parallel_mode() {
+        for c in [
+            Configuration::new().with_virtual_input_1(Input::High),
+            Configuration::new().with_virtual_input_2(Input::High),
+        ] {
+            assert_eq!(
+                c.validate(),
+                Violations::new().with_virtual_input_in_parallel_mode(true)
+            );
+            assert!(c.with_control_mode(ControlMode::Spi).validate().is_empty());
+        }
+    }
+
+    #[test]
+    fn open_load_check_outside_standby() {
+        let c = Configuration::new().with_check_for_open_load(true);
+        assert_eq!(
+            c.validate(),
+            Violations::new().with_open_load_check_outside_standby(true)
+        );
+        assert!(c.with_enable(false).validate().is_empty());
+    }
+
+    #[test]
+    fn open_load_check_in_half_bridge() {
+        let c = Configuration::new()
+            .with_enable(false)
+            .with_check_for_open_load(true)
+            .with_bridge_mode(BridgeMode::HalfBridge);
+        assert_eq!(
+            c.validate(),
+            Violations::new().with_open_load_check_in_half_bridge(true)
+        );
+        assert!(c
+            .with_bridge_mode(BridgeMode::HBridge)
+            .validate()
+            .is_empty());
+    }
+
+    #[test]
+    fn thermal_management_without_current_limit() {
+        let c = Configuration::new().with_enable_active_current_limit(false);
+        assert_eq!(
+            c.validate(),
+            Violations::new().with_thermal_management_without_current_limit(true)
+        );
+        assert!(c
+            .with_enable_thermal_management(false)
+            .validate()
+            .is_empty());
+    }
+}
@@ -3,18 +3,38 @@
 #![no_std]
 #![no_main]
 
+use core::cell::RefCell;
 use core::convert::Infallible;
 
+use cortex_m::interrupt::Mutex;
 use embedded_hal::digital::OutputPin;
 use embedded_hal::pwm::SetDutyCycle;
-use embedded_hal_async::spi::SpiDevice;
-use synch::RateExt;
+use mc33hb2001::soft_pwm::{Channel, SoftPwm};
 use woven::RaceSame;
 use {defmt_rtt as _, panic_probe as _};
 
-const ARM_FREQUENCY: u32 = 48_000_000;
-
-type Timer = synch::Timer<ARM_FREQUENCY>;
+defmt::timestamp!("{=u64:us}", hal::delay::uptime_us());
+
+type Device<B> = hal::spi::hardware_cs::HardwareCsDevice<
+    hal::spi::Spi0,
+    B,
+    hal::pins::PTD1,
+    hal::pins::PTD2,
+    hal::pins::PTD3,
+    hal::pins::PTD0,
+>;
+
+/// Software PWM and the blocking device it sends frames over, ticked from the
+/// timer interrupt.
+#[allow(clippy::type_complexity)]
+static SOFT_PWM: Mutex<RefCell<Option<(SoftPwm, Device<hal::spi::Polling>)>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Rate the software PWM is ticked at. Units of Hertz.
+const TICK_HZ: u32 = 20_000;
+/// Software PWM frequency, giving `TICK_HZ / PWM_HZ` duty steps. Units of
+/// Hertz.
+const PWM_HZ: u32 = 1_000;
 
 #[hal::entry]
 fn entry() -> ! {
@@ -26,24 +46,45 @@ async fn main() -> ! {
 
     let hal::Peripherals {
         pins,
+        tpm0,
+        tpm1,
         tpm2,
         dma0,
         dma1,
         spi0,
+        mcg,
+        cop,
         ..
-    } = unsafe { hal::Peripherals::steal() };
+    } = hal::Peripherals::take().unwrap();
+
+    // On a failure `communicate` keeps the outputs disabled until the
+    // watchdog restarts the board.
+    let clocks = match hal::clock::ClockConfig::pee(8_000_000, 48_000_000).freeze(mcg) {
+        Ok(clocks) => clocks,
+        Err(failed) => {
+            defmt::error!("Clock setup failed, using fallback: {}", failed.error);
+            failed.clocks
+        }
+    };
 
-    let core = unsafe { cortex_m::Peripherals::steal() };
+    let watchdog = cop.enable(&hal::watchdog::WatchdogConfig::default(), &clocks);
 
-    let timer = synch::Timer::new(core.SYST);
+    let delay = hal::delay::Delay::new(tpm0, &clocks);
 
-    let bus = hal::spi::SpiMaster::new(spi0, dma0, dma1, pins.PTD1, pins.PTD2, pins.PTD3);
-    let Ok(device) = embedded_hal_bus::spi::ExclusiveDevice::new_no_delay(
-        bus,
-        hal::gpio::Output::new(pins.PTD0),
-    );
+    let bus = hal::spi::SpiMaster::new(
+        spi0,
+        dma0,
+        dma1,
+        pins.PTD1,
+        pins.PTD2,
+        pins.PTD3,
+        hal::spi::SpiConfig::default(),
+        &clocks,
+    )
+    .with_timeout(delay, 1_000);
+    let device = hal::spi::hardware_cs::HardwareCsDevice::new(bus, pins.PTD0, delay);
 
-    let mut pwm2 = hal::tpm::pwm::Pwm::new(tpm2);
+    let mut pwm2 = hal::tpm::pwm::Pwm::new(tpm2, &clocks);
     let channels2 = pwm2.split();
 
     let r = channels2.channel0.use_with(pins.PTB18);
@@ -54,9 +95,12 @@ async fn main() -> ! {
             device,
             hal::gpio::Output::new(pins.PTA17),
             hal::gpio::Output::new(pins.PTE31),
-            timer,
+            delay,
+            tpm1,
+            &clocks,
+            watchdog,
         ),
-        cycle_leds(r, g, timer),
+        cycle_leds(r, g, delay),
     )
         .race_same()
         .await
@@ -65,9 +109,9 @@ async fn main() -> ! {
 async fn cycle_leds(
     mut r: impl SetDutyCycle<Error = Infallible>,
     mut g: impl SetDutyCycle<Error = Infallible>,
-    timer: Timer,
+    delay: hal::delay::Delay,
 ) -> ! {
-    let mut ticker = timer.repeat_at(1.kHz());
+    let mut ticker = delay.every_us(1_000);
 
     let mut hue: u16 = 0;
     loop {
@@ -94,39 +138,85 @@ async fn cycle_leds(
 }
 
 async fn communicate(
-    spi: impl SpiDevice<Error = embedded_hal_bus::spi::DeviceError<Infallible, Infallible>>,
+    spi: Device<hal::spi::DmaChannels<hal::dma::Dma0, hal::dma::Dma1>>,
     enable: impl OutputPin<Error = Infallible>,
     disable: impl OutputPin<Error = Infallible>,
-    timer: Timer,
+    delay: hal::delay::Delay,
+    timer: hal::tpm::Tpm1,
+    clocks: &hal::clock::Clocks,
+    mut watchdog: hal::watchdog::Watchdog,
 ) -> ! {
     const BASE_CONFIG: mc33hb2001::Configuration = mc33hb2001::Configuration::new()
         .with_bridge_mode(mc33hb2001::BridgeMode::HBridge)
         .with_control_mode(mc33hb2001::ControlMode::Spi)
         .with_virtual_input_1(mc33hb2001::Input::High);
 
-    let mut ethrottle = mc33hb2001::Driver::new(spi, enable, disable, timer)
+    let ethrottle = mc33hb2001::Driver::new(spi, enable, disable, delay)
         .await
         .unwrap();
 
-    let mut on = 100;
+    // Setup is done, so move the bus to polling for the timer interrupt. The
+    // pins stay held here, keeping the bridge enabled.
+    let (spi, _enable, _disable, _) = ethrottle.release();
+    let (bus, ss) = spi.release();
+    let (spi0, _, _, sck, mosi, miso) = bus.release();
+    let bus = hal::spi::SpiMaster::new_polling(
+        spi0,
+        sck,
+        mosi,
+        miso,
+        hal::spi::SpiConfig::default(),
+        clocks,
+    );
+    let device = hal::spi::hardware_cs::HardwareCsDevice::new(bus, ss, delay);
+
+    let periodic = hal::tpm::periodic::Periodic::new(timer, TICK_HZ, clocks, tick_soft_pwm);
+    let soft_pwm = SoftPwm::new(BASE_CONFIG, Channel::VirtualInput2, periodic.frequency_hz());
+    cortex_m::interrupt::free(|cs| SOFT_PWM.borrow(cs).replace(Some((soft_pwm, device))));
+
+    let mut ticker = delay.every_us(1_000);
+    let mut on: u16 = 100;
 
     loop {
+        if let Some(failed) = hal::clock::failure() {
+            defmt::error!("Clock failure, disabling outputs: {}", failed.error);
+
+            cortex_m::interrupt::free(|cs| {
+                if let Some((soft_pwm, device)) = SOFT_PWM.borrow(cs).borrow_mut().as_mut() {
+                    soft_pwm.disable(device).unwrap();
+                }
+            });
+
+            // Stop feeding the watchdog, so the board restarts.
+            match core::future::pending::<Infallible>().await {}
+        }
+
+        watchdog.feed();
+
         if on >= 900 {
             on = 100;
         } else {
             on += 1;
         }
 
-        timer.after(synch::Duration::micros(1000 - on)).await;
-
-        let Ok(()) = ethrottle
-            .set_configuration(BASE_CONFIG.with_virtual_input_2(mc33hb2001::Input::High))
-            .await;
+        // Duty is a fraction of `u16::MAX`, `on` a fraction of 1000.
+        let duty = u16::try_from(u32::from(u16::MAX) * u32::from(on) / 1000).unwrap();
 
-        timer.after(synch::Duration::micros(on)).await;
+        cortex_m::interrupt::free(|cs| {
+            if let Some((soft_pwm, _)) = SOFT_PWM.borrow(cs).borrow_mut().as_mut() {
+                soft_pwm.set(PWM_HZ, duty).unwrap();
+            }
+        });
 
-        let Ok(()) = ethrottle
-            .set_configuration(BASE_CONFIG.with_virtual_input_2(mc33hb2001::Input::Low))
-            .await;
+        ticker.next().await;
     }
 }
+
+fn tick_soft_pwm() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some((soft_pwm, device)) = SOFT_PWM.borrow(cs).borrow_mut().as_mut() {
+            // A failed frame is retried on the next tick.
+            let _ = soft_pwm.tick(device);
+        }
+    });
+}